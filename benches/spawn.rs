@@ -0,0 +1,92 @@
+//! Scheduler heavy workloads, run with `cargo bench --bench spawn`.
+//!
+//! To compare a change against the current code, save a baseline before making it and compare
+//! against it afterwards:
+//!
+//! ```sh
+//! cargo bench --bench spawn -- --save-baseline before
+//! cargo bench --bench spawn -- --baseline before
+//! ```
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use wae::Threadpool;
+
+fn spawn_storm(c: &mut Criterion, pool: &Threadpool) {
+    let mut group = c.benchmark_group("spawn storm");
+    group.throughput(Throughput::Elements(100_000));
+    group.bench_function("100000 tasks", |b| {
+        b.iter(|| {
+            pool.block_on(async {
+                let handles: Vec<_> = (0..100_000).map(|_| wae::spawn(async {})).collect();
+                for handle in handles {
+                    handle.await;
+                }
+            })
+        })
+    });
+    group.finish();
+}
+
+fn yield_heavy(c: &mut Criterion, pool: &Threadpool) {
+    let mut group = c.benchmark_group("yield heavy");
+    group.throughput(Throughput::Elements(100 * 1_000));
+    group.bench_function("100 tasks x 1000 yields", |b| {
+        b.iter(|| {
+            pool.block_on(async {
+                let handles: Vec<_> = (0..100)
+                    .map(|_| {
+                        wae::spawn(async {
+                            for _ in 0..1_000 {
+                                wae::task::yield_now().await;
+                            }
+                        })
+                    })
+                    .collect();
+                for handle in handles {
+                    handle.await;
+                }
+            })
+        })
+    });
+    group.finish();
+}
+
+fn ping_pong(c: &mut Criterion, pool: &Threadpool) {
+    let mut group = c.benchmark_group("ping pong");
+    group.throughput(Throughput::Elements(10_000));
+    group.bench_function("10000 rounds", |b| {
+        b.iter(|| {
+            pool.block_on(pool.spawn(async {
+                use futures::{channel::mpsc, SinkExt, StreamExt};
+
+                let (mut ping_tx, mut ping_rx) = mpsc::channel::<()>(1);
+                let (mut pong_tx, mut pong_rx) = mpsc::channel::<()>(1);
+                wae::spawn(async move {
+                    while ping_rx.next().await.is_some() {
+                        pong_tx.send(()).await.unwrap();
+                    }
+                });
+                for _ in 0..10_000 {
+                    ping_tx.send(()).await.unwrap();
+                    pong_rx.next().await.unwrap();
+                }
+            }))
+        })
+    });
+    group.finish();
+}
+
+fn benches(c: &mut Criterion) {
+    let pool = Threadpool::new().unwrap();
+    spawn_storm(c, &pool);
+    yield_heavy(c, &pool);
+    ping_pong(c, &pool);
+}
+
+criterion_group! {
+    name = spawn;
+    // Every iteration runs a whole workload, the default of 100 samples would take minutes.
+    config = Criterion::default().sample_size(20);
+    targets = benches
+}
+criterion_main!(spawn);
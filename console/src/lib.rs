@@ -0,0 +1,82 @@
+//! The state behind the `wae-console` client, rebuilt from the records a pool streams.
+
+use std::{
+    collections::BTreeMap,
+    io::{self, Write},
+    time::Duration,
+};
+
+/// What is known about a task from the records received so far.
+#[derive(Debug, Default)]
+pub struct Task {
+    pub location: String,
+    pub name: String,
+    pub wakes: u64,
+    pub polls: u64,
+    pub busy: Duration,
+    pub done: bool,
+}
+
+/// The tasks of a pool, rebuilt from its stream of records.
+#[derive(Debug, Default)]
+pub struct State {
+    pub tasks: BTreeMap<u64, Task>,
+    pub lost: u64,
+}
+
+impl State {
+    /// Applies a record, without its trailing newline. Unknown records are ignored.
+    pub fn apply(&mut self, line: &str) {
+        let mut fields = line.split('\t');
+        let kind = fields.next().unwrap_or_default();
+        let id = fields.next().and_then(|id| id.parse().ok());
+        match (kind, id) {
+            ("spawn", Some(id)) => {
+                let task = self.tasks.entry(id).or_default();
+                task.location = fields.next().unwrap_or_default().to_owned();
+                task.name = fields.next().unwrap_or_default().to_owned();
+            }
+            ("wake", Some(id)) => self.tasks.entry(id).or_default().wakes += 1,
+            ("poll", Some(id)) => {
+                let nanos = fields.next().and_then(|n| n.parse().ok()).unwrap_or(0);
+                let task = self.tasks.entry(id).or_default();
+                task.polls += 1;
+                task.busy += Duration::from_nanos(nanos);
+            }
+            ("done", Some(id)) => self.tasks.entry(id).or_default().done = true,
+            ("lost", Some(count)) => self.lost += count,
+            _ => {}
+        }
+    }
+
+    /// Draws the tasks as a table, then forgets the completed ones.
+    pub fn draw(&mut self, out: &mut impl Write) -> io::Result<()> {
+        // Clear the screen and move the cursor back to the top left corner.
+        write!(out, "\x1b[2J\x1b[H")?;
+        writeln!(
+            out,
+            "{:>8}  {:<24}  {:>8}  {:>8}  {:>12}  location",
+            "id", "name", "wakes", "polls", "busy"
+        )?;
+        for (id, task) in &self.tasks {
+            writeln!(
+                out,
+                "{:>8}  {:<24}  {:>8}  {:>8}  {:>12}  {}",
+                id,
+                task.name,
+                task.wakes,
+                task.polls,
+                format!("{:.2?}", task.busy),
+                task.location
+            )?;
+        }
+        if self.lost > 0 {
+            writeln!(out, "\n{} events lost", self.lost)?;
+        }
+        out.flush()?;
+
+        // Completed tasks are shown once before being forgotten.
+        self.tasks.retain(|_, task| !task.done);
+        Ok(())
+    }
+}
@@ -0,0 +1,63 @@
+use std::{
+    env,
+    io::{self, BufRead, BufReader},
+    net::TcpStream,
+    process,
+    sync::mpsc::{self, RecvTimeoutError},
+    thread,
+    time::{Duration, Instant},
+};
+
+use wae_console::State;
+
+const DEFAULT_ADDR: &str = "127.0.0.1:6669";
+const REFRESH: Duration = Duration::from_millis(250);
+
+fn main() {
+    let addr = env::args()
+        .nth(1)
+        .unwrap_or_else(|| DEFAULT_ADDR.to_owned());
+    let stream = match TcpStream::connect(&addr) {
+        Ok(stream) => stream,
+        Err(err) => {
+            eprintln!("failed to connect to {}: {}", addr, err);
+            process::exit(1);
+        }
+    };
+
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        for line in BufReader::new(stream).lines() {
+            match line {
+                Ok(line) => {
+                    if tx.send(line).is_err() {
+                        return;
+                    }
+                }
+                Err(_) => return,
+            }
+        }
+    });
+
+    let mut state = State::default();
+    let stdout = io::stdout();
+    let mut next_draw = Instant::now();
+    loop {
+        let timeout = next_draw.saturating_duration_since(Instant::now());
+        match rx.recv_timeout(timeout) {
+            Ok(line) => state.apply(&line),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => {
+                eprintln!("connection to {} closed", addr);
+                return;
+            }
+        }
+
+        if Instant::now() >= next_draw {
+            if state.draw(&mut stdout.lock()).is_err() {
+                return;
+            }
+            next_draw = Instant::now() + REFRESH;
+        }
+    }
+}
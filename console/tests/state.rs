@@ -0,0 +1,56 @@
+use std::time::Duration;
+
+use wae_console::State;
+
+#[test]
+fn apply() {
+    let mut state = State::default();
+    state.apply("spawn\t1\tsrc/main.rs:3:5\tworker");
+    state.apply("spawn\t2\tsrc/main.rs:4:5\t");
+    state.apply("wake\t1");
+    state.apply("poll\t1\t1500");
+    state.apply("poll\t1\t500");
+    state.apply("done\t2");
+    state.apply("lost\t3");
+    state.apply("lost\t4");
+
+    let task = &state.tasks[&1];
+    assert_eq!(task.location, "src/main.rs:3:5");
+    assert_eq!(task.name, "worker");
+    assert_eq!(task.wakes, 1);
+    assert_eq!(task.polls, 2);
+    assert_eq!(task.busy, Duration::from_nanos(2000));
+    assert!(!task.done);
+
+    let task = &state.tasks[&2];
+    assert_eq!(task.name, "");
+    assert!(task.done);
+    assert_eq!(state.lost, 7);
+}
+
+#[test]
+fn apply_ignores_unknown_records() {
+    let mut state = State::default();
+    state.apply("");
+    state.apply("exit\t1");
+    state.apply("wake\tone");
+    state.apply("poll");
+    assert!(state.tasks.is_empty());
+    assert_eq!(state.lost, 0);
+}
+
+#[test]
+fn draw_forgets_completed_tasks() {
+    let mut state = State::default();
+    state.apply("spawn\t1\tsrc/main.rs:3:5\tworker");
+    state.apply("spawn\t2\tsrc/main.rs:4:5\tdone");
+    state.apply("done\t2");
+
+    let mut out = Vec::new();
+    state.draw(&mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.contains("worker"));
+    assert!(out.contains("done"));
+
+    assert_eq!(state.tasks.keys().copied().collect::<Vec<_>>(), [1]);
+}
@@ -10,6 +10,16 @@ struct EntryArgs {
     #[darling(default)]
     min_threads: Option<u32>,
     #[darling(default)]
+    current_thread: bool,
+    #[darling(default)]
+    env: bool,
+    #[darling(default)]
+    deterministic: bool,
+    #[darling(default)]
+    seed: Option<u64>,
+    #[darling(default)]
+    start_paused: bool,
+    #[darling(default)]
     path: Option<Path>,
 }
 
@@ -59,6 +69,11 @@ fn entry(args: EntryArgs, input: ItemFn, test: bool) -> Result<TokenStream, Erro
     let EntryArgs {
         max_threads,
         min_threads,
+        current_thread,
+        env,
+        deterministic,
+        seed,
+        start_paused,
         path,
     } = args;
 
@@ -75,6 +90,7 @@ fn entry(args: EntryArgs, input: ItemFn, test: bool) -> Result<TokenStream, Erro
 
     let path = path.map(|p| quote! { #p }).unwrap_or(quote! { ::wae });
 
+    let (max_threads_set, min_threads_set) = (max_threads.is_some(), min_threads.is_some());
     let max_threads = match max_threads {
         Some(maximum) => quote! { .max_threads(#maximum) },
         None => quote! {},
@@ -84,6 +100,55 @@ fn entry(args: EntryArgs, input: ItemFn, test: bool) -> Result<TokenStream, Erro
         None => quote! {},
     };
 
+    // Deterministic pools run on the current thread too.
+    let current_thread_flavor = current_thread || deterministic || seed.is_some();
+
+    // `WAE_SEED` replays a seed printed by a failing run.
+    let deterministic = if deterministic || seed.is_some() {
+        let seed = match seed {
+            Some(seed) => quote! { #seed },
+            None => quote! {
+                ::std::hash::BuildHasher::hash_one(
+                    &::std::collections::hash_map::RandomState::new(),
+                    0u8,
+                )
+            },
+        };
+        quote! {
+            .deterministic(match ::std::env::var("WAE_SEED") {
+                Ok(seed) => seed.parse().expect("invalid WAE_SEED"),
+                Err(_) => #seed,
+            })
+        }
+    } else {
+        quote! {}
+    };
+
+    // The paused clock only skips ahead on its own when tasks run on the current thread, so
+    // `start_paused` picks that flavor unless threads were asked for.
+    if start_paused && !current_thread_flavor && (max_threads_set || min_threads_set) {
+        let msg = "`start_paused` requires a current thread pool, which has no thread bounds";
+        return Err(Error::new_spanned(sig.fn_token, msg));
+    }
+    let current_thread = if current_thread || (start_paused && !current_thread_flavor) {
+        quote! { .current_thread() }
+    } else {
+        quote! {}
+    };
+
+    let start_paused = if start_paused {
+        quote! { .start_paused(true) }
+    } else {
+        quote! {}
+    };
+
+    // Environment variables override the attribute's arguments.
+    let env = if env {
+        quote! { .with_env().unwrap_or_else(|err| panic!("{}", err)) }
+    } else {
+        quote! {}
+    };
+
     let output = quote! {
         #header
         #(#attrs)*
@@ -91,6 +156,10 @@ fn entry(args: EntryArgs, input: ItemFn, test: bool) -> Result<TokenStream, Erro
             #path::Threadpool::builder()
                 #max_threads
                 #min_threads
+                #current_thread
+                #deterministic
+                #start_paused
+                #env
                 .build()
                 .unwrap()
                 .block_on(async #block)
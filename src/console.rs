@@ -0,0 +1,196 @@
+//! Live runtime inspection for the `wae-console` client.
+//!
+//! When enabled through [`Builder::console`](crate::threadpool::Builder::console), the pool serves
+//! a stream of task events to every client connected to the given address. The stream is UTF-8
+//! text made of newline terminated records whose fields are separated by tabs, the first field
+//! being the record kind.
+//!
+//! | record                                  | meaning                                         |
+//! | --------------------------------------- | ----------------------------------------------- |
+//! | `spawn <id> <location> <name>`          | a task was spawned, `name` may be empty         |
+//! | `wake <id>`                             | a task was woken up and scheduled               |
+//! | `poll <id> <nanos>`                     | a task was polled for `nanos` nanoseconds       |
+//! | `done <id>`                             | a task completed or was dropped                 |
+//! | `lost <count>`                          | `count` events were dropped before being served |
+//!
+//! Tabs and newlines in names and locations are replaced with spaces. Clients that don't keep up
+//! with the stream miss records instead of slowing down the pool.
+
+use std::{
+    future::{self, Future},
+    io::Write,
+    panic::Location,
+    pin::Pin,
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+    task::{Context, Poll},
+};
+
+use atomic_waker::AtomicWaker;
+use concurrent_queue::ConcurrentQueue;
+
+use crate::{
+    io::{AsyncWrite, IoSlice},
+    net::{TcpListener, TcpStream},
+};
+
+pub(crate) struct Console {
+    events: ConcurrentQueue<Event>,
+    lost: AtomicU64,
+    waker: AtomicWaker,
+    closed: AtomicBool,
+}
+
+pub(crate) enum Event {
+    Spawn {
+        id: u64,
+        location: &'static Location<'static>,
+        name: Option<String>,
+    },
+    Wake {
+        id: u64,
+    },
+    Poll {
+        id: u64,
+        nanos: u64,
+    },
+    Done {
+        id: u64,
+    },
+}
+
+struct Client {
+    stream: TcpStream,
+    writing: Vec<u8>,
+    written: usize,
+    pending: Vec<u8>,
+}
+
+impl Console {
+    const CAPACITY: usize = 16 * 1024;
+    const CLIENT_BUFFER: usize = 1024 * 1024;
+
+    pub(crate) fn new() -> Self {
+        Self {
+            events: ConcurrentQueue::bounded(Self::CAPACITY),
+            lost: AtomicU64::new(0),
+            waker: AtomicWaker::new(),
+            closed: AtomicBool::new(false),
+        }
+    }
+
+    pub(crate) fn emit(&self, event: Event) {
+        if self.events.push(event).is_err() {
+            self.lost.fetch_add(1, Ordering::Relaxed);
+        }
+        self.waker.wake();
+    }
+
+    /// Stops the server, which otherwise runs as long as the pool.
+    pub(crate) fn close(&self) {
+        self.closed.store(true, Ordering::Release);
+        self.waker.wake();
+    }
+
+    pub(crate) async fn serve(&self, listener: TcpListener) {
+        let mut accept = listener.accept();
+        let mut clients: Vec<Client> = Vec::new();
+
+        future::poll_fn(|cx| {
+            self.waker.register(cx.waker());
+            if self.closed.load(Ordering::Acquire) {
+                return Poll::Ready(());
+            }
+
+            loop {
+                match Pin::new(&mut accept).poll(cx) {
+                    Poll::Ready(Ok((stream, _))) => {
+                        clients.push(Client::new(stream));
+                        accept = listener.accept();
+                    }
+                    Poll::Ready(Err(_err)) => {
+                        #[cfg(feature = "tracing")]
+                        tracing::debug!(error = %_err, "console accept failed");
+                        accept = listener.accept();
+                        break;
+                    }
+                    Poll::Pending => break,
+                }
+            }
+
+            let lost = self.lost.swap(0, Ordering::Relaxed);
+            let mut record = Vec::new();
+            if lost > 0 {
+                writeln!(record, "lost\t{}", lost).unwrap();
+            }
+            while let Ok(event) = self.events.pop() {
+                event.write(&mut record);
+            }
+            for client in &mut clients {
+                client.queue(&record);
+            }
+
+            clients.retain_mut(|client| client.poll_flush(cx).is_ok());
+            Poll::Pending
+        })
+        .await
+    }
+}
+
+impl Event {
+    fn write(&self, buf: &mut Vec<u8>) {
+        match self {
+            Event::Spawn { id, location, name } => {
+                let location = escape(&location.to_string());
+                let name = escape(name.as_deref().unwrap_or(""));
+                writeln!(buf, "spawn\t{}\t{}\t{}", id, location, name)
+            }
+            Event::Wake { id } => writeln!(buf, "wake\t{}", id),
+            Event::Poll { id, nanos } => writeln!(buf, "poll\t{}\t{}", id, nanos),
+            Event::Done { id } => writeln!(buf, "done\t{}", id),
+        }
+        .unwrap()
+    }
+}
+
+impl Client {
+    fn new(stream: TcpStream) -> Self {
+        Self {
+            stream,
+            writing: Vec::new(),
+            written: 0,
+            pending: Vec::new(),
+        }
+    }
+
+    fn queue(&mut self, record: &[u8]) {
+        // Slow clients lose events rather than growing the buffer forever.
+        if self.pending.len() + record.len() <= Console::CLIENT_BUFFER {
+            self.pending.extend_from_slice(record);
+        }
+    }
+
+    fn poll_flush(&mut self, cx: &mut Context<'_>) -> Result<(), ()> {
+        loop {
+            if self.written == self.writing.len() {
+                if self.pending.is_empty() {
+                    return Ok(());
+                }
+                self.writing.clear();
+                self.written = 0;
+                std::mem::swap(&mut self.writing, &mut self.pending);
+            }
+
+            // `writing` isn't touched until the write completes so the buffer stays valid.
+            let buf = IoSlice::from(&self.writing[self.written..]);
+            match unsafe { Pin::new(&mut self.stream).poll_write(cx, &buf) } {
+                Poll::Ready(Ok(0)) | Poll::Ready(Err(_)) => return Err(()),
+                Poll::Ready(Ok(n)) => self.written += n,
+                Poll::Pending => return Ok(()),
+            }
+        }
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace(['\t', '\n', '\r'], " ")
+}
@@ -1,13 +1,27 @@
 use std::{cell::RefCell, fmt, marker::PhantomData};
+#[cfg(feature = "global")]
+use std::{io, sync::OnceLock};
 
 use crate::threadpool::Handle;
+#[cfg(feature = "global")]
+use crate::threadpool::{Builder, Threadpool};
 
 thread_local! {
-    static HANDLE: RefCell<Option<Handle>> = RefCell::new(None);
+    static HANDLE: RefCell<Option<Entered>> = const { RefCell::new(None) };
 }
 
+/// The handle of an entered context, borrowed while polling a task since its metadata already
+/// holds one.
+enum Entered {
+    Owned(Handle),
+    Borrowed(*const Handle),
+}
+
+#[cfg(feature = "global")]
+static GLOBAL: OnceLock<Threadpool> = OnceLock::new();
+
 pub struct ContextGuard<'a> {
-    previous: Option<Handle>,
+    previous: Option<Entered>,
     _marker: PhantomData<&'a Handle>,
 }
 
@@ -27,21 +41,44 @@ impl fmt::Debug for ContextGuard<'_> {
 }
 
 impl Handle {
+    /// Returns the handle of the context entered on this thread.
+    ///
+    /// With the `global` feature, the global default pool is used outside of any context and
+    /// gets created the first time it's needed.
     pub fn current() -> Handle {
-        Self::try_current().expect("no wae context")
+        match Self::try_current() {
+            Some(handle) => handle,
+            #[cfg(feature = "global")]
+            None => Handle::clone(global()),
+            #[cfg(not(feature = "global"))]
+            None => panic!("no wae context"),
+        }
     }
 
+    /// Returns the handle of the context entered on this thread, ignoring the global default pool.
     pub fn try_current() -> Option<Handle> {
         HANDLE.with(|h| {
             let h = h.borrow();
-            h.clone()
+            h.as_ref().map(|entered| entered.handle().clone())
         })
     }
 
     pub fn enter(&self) -> ContextGuard<'_> {
+        self.enter_with(Entered::Owned(self.clone()))
+    }
+
+    /// Enters the context without cloning the handle.
+    ///
+    /// The guard must be dropped rather than forgotten, as the context would still point to the
+    /// handle otherwise.
+    pub(crate) fn enter_borrowed(&self) -> ContextGuard<'_> {
+        self.enter_with(Entered::Borrowed(self))
+    }
+
+    fn enter_with(&self, entered: Entered) -> ContextGuard<'_> {
         HANDLE.with(|h| {
             let mut h = h.borrow_mut();
-            let previous = h.replace(self.clone());
+            let previous = h.replace(entered);
             ContextGuard {
                 previous,
                 _marker: PhantomData::default(),
@@ -53,8 +90,54 @@ impl Handle {
     pub(crate) fn enter_span(&self) -> Option<tracing::span::Entered<'_>> {
         self.span.as_ref().map(|span| span.enter())
     }
+
+    #[cfg(feature = "tracing")]
+    pub(crate) fn span_id(&self) -> Option<tracing::span::Id> {
+        self.span.as_ref().and_then(|span| span.id())
+    }
 }
 
 pub fn current() -> Handle {
     Handle::current()
 }
+
+impl Entered {
+    fn handle(&self) -> &Handle {
+        match self {
+            Entered::Owned(handle) => handle,
+            // Only set for as long as the guard borrowing the handle lives.
+            Entered::Borrowed(handle) => unsafe { &**handle },
+        }
+    }
+}
+
+/// Builds the pool used by [`Handle::current`] outside of any context instead of the default one.
+///
+/// Fails if the global default pool already exists, or if `builder` is a
+/// [`Builder::current_thread`] pool since nothing would drive it.
+#[cfg(feature = "global")]
+pub fn set_global_default(builder: Builder) -> io::Result<()> {
+    let already_set = || {
+        io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            "the global default pool is already initialized",
+        )
+    };
+    if GLOBAL.get().is_some() {
+        return Err(already_set());
+    }
+
+    let pool = builder.build()?;
+    if pool.driver().is_some() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "the global default pool can't be a current thread pool",
+        ));
+    }
+    GLOBAL.set(pool).map_err(|_| already_set())
+}
+
+#[cfg(feature = "global")]
+fn global() -> &'static Threadpool {
+    GLOBAL.get_or_init(|| Threadpool::new().expect("failed to create the global default pool"))
+}
@@ -0,0 +1,243 @@
+//! Wrappers injecting faults into IO objects, to test code against hostile transports.
+//!
+//! A [`Faulty`] wrapper decides what happens to each read and write as it starts, following the
+//! [`Schedule`] of its direction: scripted faults first, then seeded random ones.
+
+use std::{
+    collections::VecDeque,
+    fmt, io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use pin_project_lite::pin_project;
+
+use super::{AsyncRead, AsyncWrite, IoSlice, IoSliceMut};
+
+/// What happens to a single read or write.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// Passes the operation through.
+    None,
+    /// Transfers at most this many bytes, at least one unless the buffer is empty.
+    Short(usize),
+    /// Returns `Poll::Pending` this many times first, waking the task right away each time.
+    Delay(u32),
+    /// Fails with this raw OS error.
+    Os(i32),
+    /// Fails with an error of this kind.
+    Error(io::ErrorKind),
+    /// Fails with [`io::ErrorKind::ConnectionReset`], along with every later read and write.
+    Reset,
+}
+
+/// The faults to inject into the reads or writes of a [`Faulty`] wrapper.
+#[derive(Debug, Clone, Default)]
+pub struct Schedule {
+    script: VecDeque<Fault>,
+    random: Vec<(f64, Fault)>,
+    rng: u64,
+}
+
+pin_project! {
+    /// Injects faults into the reads and writes of `T`.
+    pub struct Faulty<T> {
+        #[pin]
+        io: T,
+        reads: Schedule,
+        writes: Schedule,
+        read: Option<Fault>,
+        write: Option<Fault>,
+        reset: bool,
+    }
+}
+
+impl Schedule {
+    /// Creates a schedule passing every operation through.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Uses `fault` for the next operation without a scripted fault yet.
+    pub fn then(mut self, fault: Fault) -> Schedule {
+        self.script.push_back(fault);
+        self
+    }
+
+    /// Uses `fault` with the given probability for the operations after the scripted ones.
+    ///
+    /// Random faults are tried in the order they were added and the first hit is used.
+    pub fn random(mut self, probability: f64, fault: Fault) -> Schedule {
+        self.random.push((probability, fault));
+        self
+    }
+
+    /// Seeds the random faults so a given seed always injects the same ones, 0 by default.
+    pub fn seed(mut self, seed: u64) -> Schedule {
+        self.rng = seed;
+        self
+    }
+
+    fn next(&mut self) -> Fault {
+        if let Some(fault) = self.script.pop_front() {
+            return fault;
+        }
+        for i in 0..self.random.len() {
+            let (probability, fault) = self.random[i];
+            if self.sample() < probability {
+                return fault;
+            }
+        }
+        Fault::None
+    }
+
+    /// Returns a number in `[0, 1)` from splitmix64.
+    fn sample(&mut self) -> f64 {
+        self.rng = self.rng.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.rng;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^= z >> 31;
+        (z >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+impl<T> Faulty<T> {
+    /// Wraps `io` without injecting any fault yet.
+    pub fn new(io: T) -> Self {
+        Self {
+            io,
+            reads: Schedule::new(),
+            writes: Schedule::new(),
+            read: None,
+            write: None,
+            reset: false,
+        }
+    }
+
+    pub fn reads(mut self, schedule: Schedule) -> Self {
+        self.reads = schedule;
+        self
+    }
+
+    pub fn writes(mut self, schedule: Schedule) -> Self {
+        self.writes = schedule;
+        self
+    }
+
+    pub fn get_ref(&self) -> &T {
+        &self.io
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.io
+    }
+
+    pub fn into_inner(self) -> T {
+        self.io
+    }
+}
+
+/// Applies the fault of the operation in progress, deciding it first if there is none.
+///
+/// The fault sticks until the operation completes so the inner object keeps seeing the same
+/// buffer, as the `poll_read` and `poll_write` contracts require. `transfer` is called with the
+/// maximum number of bytes to transfer, if limited.
+fn poll_fault(
+    current: &mut Option<Fault>,
+    schedule: &mut Schedule,
+    reset: &mut bool,
+    cx: &mut Context<'_>,
+    transfer: impl FnOnce(&mut Context<'_>, Option<usize>) -> Poll<io::Result<usize>>,
+) -> Poll<io::Result<usize>> {
+    if *reset {
+        return Poll::Ready(Err(io::ErrorKind::ConnectionReset.into()));
+    }
+    let fault = current.get_or_insert_with(|| schedule.next());
+    let poll = match *fault {
+        Fault::None => transfer(cx, None),
+        Fault::Short(n) => transfer(cx, Some(n.max(1))),
+        Fault::Delay(0) => {
+            *fault = Fault::None;
+            transfer(cx, None)
+        }
+        Fault::Delay(n) => {
+            *fault = Fault::Delay(n - 1);
+            cx.waker().wake_by_ref();
+            return Poll::Pending;
+        }
+        Fault::Os(code) => Poll::Ready(Err(io::Error::from_raw_os_error(code))),
+        Fault::Error(kind) => Poll::Ready(Err(kind.into())),
+        Fault::Reset => {
+            *reset = true;
+            Poll::Ready(Err(io::ErrorKind::ConnectionReset.into()))
+        }
+    };
+    if poll.is_ready() {
+        *current = None;
+    }
+    poll
+}
+
+impl<T: AsyncRead> AsyncRead for Faulty<T> {
+    unsafe fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut IoSliceMut<'_>,
+    ) -> Poll<io::Result<usize>> {
+        let this = self.project();
+        let io = this.io;
+        poll_fault(this.read, this.reads, this.reset, cx, |cx, max| match max {
+            Some(max) if max < buf.len() => {
+                let mut short = IoSliceMut::from(&mut buf[..max]);
+                io.poll_read(cx, &mut short)
+            }
+            _ => io.poll_read(cx, buf),
+        })
+    }
+
+    fn cancel_read(self: Pin<&mut Self>, wait: bool) -> io::Result<()> {
+        let this = self.project();
+        // The next read is a new operation with a fault of its own.
+        *this.read = None;
+        this.io.cancel_read(wait)
+    }
+}
+
+impl<T: AsyncWrite> AsyncWrite for Faulty<T> {
+    unsafe fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &IoSlice<'_>,
+    ) -> Poll<io::Result<usize>> {
+        let this = self.project();
+        let io = this.io;
+        poll_fault(
+            this.write,
+            this.writes,
+            this.reset,
+            cx,
+            |cx, max| match max {
+                Some(max) if max < buf.len() => io.poll_write(cx, &IoSlice::from(&buf[..max])),
+                _ => io.poll_write(cx, buf),
+            },
+        )
+    }
+
+    fn cancel_write(self: Pin<&mut Self>, wait: bool) -> io::Result<()> {
+        let this = self.project();
+        *this.write = None;
+        this.io.cancel_write(wait)
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Faulty<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Faulty")
+            .field("io", &self.io)
+            .field("reads", &self.reads)
+            .field("writes", &self.writes)
+            .field("reset", &self.reset)
+            .finish()
+    }
+}
@@ -0,0 +1,274 @@
+//! A scripted IO object for testing protocol code, see [`Builder`].
+
+use std::{
+    collections::VecDeque,
+    fmt,
+    future::Future,
+    io,
+    mem::MaybeUninit,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+    thread,
+    time::Duration,
+};
+
+use super::{AsyncRead, AsyncWrite, IoSlice, IoSliceMut};
+use crate::time::{self, Sleep};
+
+/// Builds a [`Mock`] following a script of reads, writes and waits.
+///
+/// ```ignore
+/// let mock = Builder::new()
+///     .read(b"PING\r\n")
+///     .write(b"PONG\r\n")
+///     .wait(Duration::from_secs(1))
+///     .read_error(io::ErrorKind::ConnectionReset)
+///     .build();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Builder {
+    actions: VecDeque<Action>,
+}
+
+/// An object that reads and expects to be written what its script says, in order.
+///
+/// Reads wait for the writes scripted before them, and return the end of the stream once the
+/// script is done. Writing anything else than the next scripted write panics with a diff, and so
+/// does dropping the mock before the end of its script.
+pub struct Mock {
+    actions: VecDeque<Action>,
+    sleep: Option<Sleep>,
+    written: usize,
+    reader: Option<Waker>,
+    writer: Option<Waker>,
+}
+
+#[derive(Debug, Clone)]
+enum Action {
+    Read(Vec<u8>),
+    Write(Vec<u8>),
+    Wait(Duration),
+    ReadError(io::ErrorKind),
+    WriteError(io::ErrorKind),
+}
+
+impl Builder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Makes the next reads return `data`, possibly over several reads.
+    pub fn read(mut self, data: &[u8]) -> Builder {
+        self.actions.push_back(Action::Read(data.to_vec()));
+        self
+    }
+
+    /// Expects the next writes to be `data`, possibly over several writes.
+    pub fn write(mut self, data: &[u8]) -> Builder {
+        self.actions.push_back(Action::Write(data.to_vec()));
+        self
+    }
+
+    /// Makes reads and writes wait for `duration` on the clock of the current pool.
+    pub fn wait(mut self, duration: Duration) -> Builder {
+        self.actions.push_back(Action::Wait(duration));
+        self
+    }
+
+    /// Makes the next read fail with an error of this kind.
+    pub fn read_error(mut self, kind: io::ErrorKind) -> Builder {
+        self.actions.push_back(Action::ReadError(kind));
+        self
+    }
+
+    /// Makes the next write fail with an error of this kind.
+    pub fn write_error(mut self, kind: io::ErrorKind) -> Builder {
+        self.actions.push_back(Action::WriteError(kind));
+        self
+    }
+
+    pub fn build(self) -> Mock {
+        Mock {
+            actions: self.actions,
+            sleep: None,
+            written: 0,
+            reader: None,
+            writer: None,
+        }
+    }
+}
+
+impl Mock {
+    /// Waits out the scripted waits at the front of the script.
+    fn poll_wait(&mut self, cx: &mut Context<'_>, reading: bool) -> Poll<()> {
+        while let Some(Action::Wait(duration)) = self.actions.front() {
+            let duration = *duration;
+            let sleep = self.sleep.get_or_insert_with(|| time::sleep(duration));
+            if Pin::new(sleep).poll(cx).is_pending() {
+                // Only the last waker given to the sleep is woken up, which then wakes this one.
+                let waker = Some(cx.waker().clone());
+                if reading {
+                    self.reader = waker;
+                } else {
+                    self.writer = waker;
+                }
+                return Poll::Pending;
+            }
+            self.sleep = None;
+            self.next();
+        }
+        Poll::Ready(())
+    }
+
+    /// Moves on to the next action, waking up the other direction in case it was waiting.
+    fn next(&mut self) {
+        self.actions.pop_front();
+        for waker in self.reader.take().into_iter().chain(self.writer.take()) {
+            waker.wake();
+        }
+    }
+
+    #[track_caller]
+    fn unexpected_write(&self, buf: &[u8]) -> ! {
+        let expected = match self.actions.front() {
+            Some(Action::Read(data)) => format!("a read of {}", escape(data)),
+            Some(Action::ReadError(kind)) => format!("a read failing with {:?}", kind),
+            None => "the end of the script".to_owned(),
+            Some(_) => unreachable!(),
+        };
+        panic!(
+            "unexpected write of {} at offset {}, expected {}",
+            escape(buf),
+            self.written,
+            expected
+        )
+    }
+}
+
+impl AsyncRead for Mock {
+    unsafe fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut IoSliceMut<'_>,
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        if this.poll_wait(cx, true).is_pending() {
+            return Poll::Pending;
+        }
+
+        match this.actions.front_mut() {
+            Some(Action::Read(data)) => {
+                let n = buf.len().min(data.len());
+                for (dst, src) in buf.iter_mut().zip(data.drain(..n)) {
+                    *dst = MaybeUninit::new(src);
+                }
+                if data.is_empty() {
+                    this.next();
+                }
+                Poll::Ready(Ok(n))
+            }
+            Some(Action::ReadError(kind)) => {
+                let kind = *kind;
+                this.next();
+                Poll::Ready(Err(kind.into()))
+            }
+            Some(_) => {
+                this.reader = Some(cx.waker().clone());
+                Poll::Pending
+            }
+            None => Poll::Ready(Ok(0)),
+        }
+    }
+
+    fn cancel_read(self: Pin<&mut Self>, _wait: bool) -> io::Result<()> {
+        // Reads only ever wait on the script, which a cancelled read doesn't move forward.
+        self.get_mut().reader = None;
+        Ok(())
+    }
+}
+
+impl AsyncWrite for Mock {
+    unsafe fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &IoSlice<'_>,
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        if this.poll_wait(cx, false).is_pending() {
+            return Poll::Pending;
+        }
+
+        match this.actions.front_mut() {
+            Some(Action::Write(expected)) => {
+                let n = buf.len().min(expected.len());
+                let same = common_prefix(expected, buf);
+                if same < n {
+                    panic!(
+                        "unexpected bytes written at offset {}\n{}",
+                        this.written + same,
+                        diff(expected, buf, same)
+                    );
+                }
+                expected.drain(..n);
+                this.written += n;
+                if expected.is_empty() {
+                    this.next();
+                }
+                Poll::Ready(Ok(n))
+            }
+            Some(Action::WriteError(kind)) => {
+                let kind = *kind;
+                this.next();
+                Poll::Ready(Err(kind.into()))
+            }
+            _ if buf.is_empty() => Poll::Ready(Ok(0)),
+            _ => this.unexpected_write(buf),
+        }
+    }
+
+    fn cancel_write(self: Pin<&mut Self>, _wait: bool) -> io::Result<()> {
+        self.get_mut().writer = None;
+        Ok(())
+    }
+}
+
+impl Drop for Mock {
+    fn drop(&mut self) {
+        if !self.actions.is_empty() && !thread::panicking() {
+            panic!(
+                "mock dropped with {} actions left: {:?}",
+                self.actions.len(),
+                self.actions
+            );
+        }
+    }
+}
+
+impl fmt::Debug for Mock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Mock")
+            .field("actions", &self.actions)
+            .field("written", &self.written)
+            .finish()
+    }
+}
+
+fn escape(bytes: &[u8]) -> String {
+    format!("b\"{}\"", bytes.escape_ascii())
+}
+
+fn common_prefix(a: &[u8], b: &[u8]) -> usize {
+    a.iter().zip(b).take_while(|(a, b)| a == b).count()
+}
+
+/// Lines up the expected and written bytes, pointing at the first difference.
+fn diff(expected: &[u8], actual: &[u8], same: usize) -> String {
+    let offset = "expected: b\"".len() + expected[..same].escape_ascii().count();
+    format!(
+        "expected: {}\n written: {}\n{:>width$}",
+        escape(expected),
+        escape(actual),
+        "^",
+        width = offset + 1
+    )
+}
@@ -2,6 +2,10 @@ pub mod read;
 pub mod write;
 
 mod cancel;
+#[cfg(feature = "io-fault")]
+pub mod fault;
+#[cfg(feature = "io-mock")]
+pub mod mock;
 #[cfg(feature = "io-shared")]
 pub(crate) mod shared;
 
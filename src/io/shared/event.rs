@@ -2,6 +2,7 @@ use atomic_waker::AtomicWaker;
 use std::{
     ffi::c_void,
     io, ptr,
+    sync::Arc,
     task::{Context, Poll},
     thread,
 };
@@ -9,20 +10,27 @@ use winapi::{
     shared::minwindef::{FALSE, TRUE},
     um::{
         handleapi::CloseHandle,
-        ioapiset::GetOverlappedResult,
+        ioapiset::{CancelIoEx, GetOverlappedResult},
         minwinbase::OVERLAPPED,
         synchapi::{CreateEventW, ResetEvent},
         threadpoolapiset::{CloseThreadpoolWait, CreateThreadpoolWait, SetThreadpoolWait},
-        winnt::{HANDLE, PTP_CALLBACK_INSTANCE, PTP_WAIT, TP_CALLBACK_ENVIRON_V3, TP_WAIT_RESULT},
+        winnt::{HANDLE, PTP_CALLBACK_INSTANCE, PTP_WAIT, TP_WAIT_RESULT},
     },
 };
 
-use crate::threadpool::Handle;
+use crate::{
+    task::TaskInfo,
+    threadpool::{Handle, Hooks, WeakHandle},
+};
 
 use super::{IoResult, IoState};
 
 pub(crate) struct IoEvent {
     wait: PTP_WAIT,
+    pool: WeakHandle,
+    hooks: Arc<Hooks>,
+    /// Created by one of the pool's own tasks, so left out of its pending IO.
+    internal: bool,
     state: IoState,
     result: IoResult,
     waker: AtomicWaker,
@@ -40,21 +48,36 @@ unsafe extern "system" fn callback(
 ) {
     let context = context as *const IoEvent;
     let event = &*context;
+    event.hooks.thread_started();
+    // The event can be dropped as soon as it is ready.
+    let pool = event.pool.callback_pool();
+    let internal = event.internal;
     if event.state.callback_pending() {
         event.result.set(result, 0);
         event.state.set_ready();
         event.waker.wake();
+    } else if event.state.callback_cancelled_wait() {
+        event.state.set_ready();
+    } else {
+        return;
+    }
+    // Only once the task is woken up so the pool never looks idle in between.
+    if !internal {
+        pool.io_event_completed();
     }
 }
 
 impl IoEvent {
-    pub(crate) fn new(callback_environ: &TP_CALLBACK_ENVIRON_V3) -> io::Result<Box<Self>> {
+    pub(crate) fn new(pool: &Handle) -> io::Result<Box<Self>> {
         let event = unsafe { CreateEventW(ptr::null_mut(), TRUE, FALSE, ptr::null_mut()) };
         if event.is_null() {
             return Err(io::Error::last_os_error());
         }
         let mut this = Box::new(Self {
             wait: ptr::null_mut(),
+            pool: pool.downgrade(),
+            hooks: pool.hooks().clone(),
+            internal: TaskInfo::current_is_internal(),
             state: IoState::new(),
             result: IoResult::new(),
             waker: AtomicWaker::new(),
@@ -64,11 +87,12 @@ impl IoEvent {
             },
         });
 
+        let mut callback_environ = pool.callback_environ();
         let wait = unsafe {
             CreateThreadpoolWait(
                 Some(callback),
                 &mut *this as *mut Self as *mut c_void,
-                callback_environ as *const TP_CALLBACK_ENVIRON_V3 as *mut TP_CALLBACK_ENVIRON_V3,
+                &mut callback_environ,
             )
         };
         if wait.is_null() {
@@ -130,6 +154,13 @@ impl IoEvent {
                 }
             }
         } else if self.state.schedule() {
+            let pool = match self.pool.upgrade_live() {
+                Ok(pool) => pool,
+                Err(err) => {
+                    self.state.set_idle();
+                    return Poll::Ready(Err(err));
+                }
+            };
             let poll = schedule(&self.overlapped as *const OVERLAPPED as *mut OVERLAPPED);
             match poll {
                 Poll::Ready(result) => {
@@ -138,6 +169,9 @@ impl IoEvent {
                 }
                 Poll::Pending => {
                     self.waker.register(cx.waker());
+                    if !self.internal {
+                        pool.io_event_started();
+                    }
                     self.state.set_pending();
                     Poll::Pending
                 }
@@ -147,6 +181,26 @@ impl IoEvent {
             Poll::Pending
         }
     }
+
+    /// Cancels the pending operation on `handle` and waits for it to complete, as the event can't
+    /// be dropped before then.
+    pub(crate) fn cancel(&self, handle: HANDLE) {
+        if !self.state.is_cancellable() {
+            return;
+        }
+        if self.state.cancel(true) {
+            Handle::try_current().map(|h| h.may_block());
+            unsafe {
+                CancelIoEx(
+                    handle,
+                    &self.overlapped as *const OVERLAPPED as *mut OVERLAPPED,
+                );
+            }
+        }
+        while self.state.is_busy() {
+            thread::yield_now();
+        }
+    }
 }
 
 impl Drop for IoEvent {
@@ -156,7 +210,8 @@ impl Drop for IoEvent {
             thread::yield_now();
         }
         unsafe {
-            if !self.wait.is_null() {
+            // Like IO objects, waits are closed by the pool's cleanup group once it is gone.
+            if let Some(_pool) = self.pool.upgrade().filter(|_| !self.wait.is_null()) {
                 CloseThreadpoolWait(self.wait);
             }
             CloseHandle(self.overlapped.hEvent);
@@ -4,21 +4,25 @@ use std::{
     sync::Arc,
     task::{Context, Poll},
     thread,
+    time::Instant,
 };
 use winapi::{
     shared::ws2def::WSABUF,
     um::{
         minwinbase::OVERLAPPED,
         threadpoolapiset::{CloseThreadpoolIo, CreateThreadpoolIo, StartThreadpoolIo},
-        winnt::{HANDLE, PTP_CALLBACK_INSTANCE, PTP_IO, TP_CALLBACK_ENVIRON_V3},
+        winnt::{HANDLE, PTP_CALLBACK_INSTANCE, PTP_IO},
     },
 };
 
 use atomic_waker::AtomicWaker;
 use cache_padded::CachePadded;
 
-use super::{IoResult, IoState};
-use crate::threadpool::Handle;
+use super::{IoResult, IoState, IoStats};
+use crate::{
+    task::TaskInfo,
+    threadpool::{Handle, Hooks, WeakHandle},
+};
 
 type ScheduleFn = unsafe fn(HANDLE, *mut OVERLAPPED, *mut WSABUF) -> Poll<io::Result<usize>>;
 type CancelFn = unsafe fn(HANDLE, *mut OVERLAPPED, bool) -> io::Result<()>;
@@ -28,6 +32,9 @@ pub(crate) struct IoHandle {
     pub(crate) handle: HANDLE,
     ptp_io: PTP_IO,
     close: CloseFn,
+    pool: WeakHandle,
+    hooks: Arc<Hooks>,
+    created: Instant,
     read: CachePadded<IoHalf>,
     write: CachePadded<IoHalf>,
 }
@@ -39,6 +46,7 @@ struct IoHalf {
     state: IoState,
     result: IoResult,
     waker: AtomicWaker,
+    stats: IoStats,
     overlapped: OVERLAPPED,
     schedule: ScheduleFn,
     cancel: CancelFn,
@@ -53,6 +61,7 @@ unsafe extern "system" fn callback(
     _io: PTP_IO,
 ) {
     let context = &*(context as *const IoHandle);
+    context.hooks.thread_started();
     let overlapped = overlapped as *const OVERLAPPED;
     let half = if overlapped == &context.read.overlapped {
         &*context.read
@@ -62,6 +71,17 @@ unsafe extern "system" fn callback(
         unreachable!()
     };
 
+    #[cfg(feature = "tracing")]
+    tracing::trace!(
+        handle = ?context.handle,
+        direction = context.direction(half),
+        transferred,
+        os_error = result,
+        "io completed"
+    );
+
+    // The handle can be dropped as soon as the operation is ready.
+    let pool = context.pool.callback_pool();
     if half.state.callback_pending() || half.state.callback_cancelled_nowait() {
         half.result.set(result, transferred);
         half.state.set_ready();
@@ -69,6 +89,7 @@ unsafe extern "system" fn callback(
     } else if half.state.callback_cancelled_wait() {
         half.state.set_ready()
     }
+    pool.notify_idle();
 }
 
 impl IoHandle {
@@ -79,22 +100,26 @@ impl IoHandle {
         cancel_read: CancelFn,
         schedule_write: ScheduleFn,
         cancel_write: CancelFn,
-        callback_environ: &TP_CALLBACK_ENVIRON_V3,
+        pool: &Handle,
     ) -> io::Result<Arc<Self>> {
         let mut this = Arc::new(IoHandle {
             handle,
             ptp_io: ptr::null_mut(),
+            pool: pool.downgrade(),
+            hooks: pool.hooks().clone(),
+            created: Instant::now(),
             read: CachePadded::new(IoHalf::new(schedule_read, cancel_read)),
             write: CachePadded::new(IoHalf::new(schedule_write, cancel_write)),
             close,
         });
 
+        let mut callback_environ = pool.callback_environ();
         let ptp_io = unsafe {
             CreateThreadpoolIo(
                 handle,
                 Some(callback),
                 &*this as *const Self as *mut c_void,
-                callback_environ as *const TP_CALLBACK_ENVIRON_V3 as *mut TP_CALLBACK_ENVIRON_V3,
+                &mut callback_environ,
             )
         };
         if ptp_io.is_null() {
@@ -102,6 +127,9 @@ impl IoHandle {
         }
 
         Arc::get_mut(&mut this).unwrap().ptp_io = ptp_io;
+        if !TaskInfo::current_is_internal() {
+            pool.register_io(&this);
+        }
         Ok(this)
     }
 
@@ -113,9 +141,27 @@ impl IoHandle {
     ) -> Poll<io::Result<usize>> {
         if half.state.finish() {
             let result = half.result.get();
+            half.stats.record(&result, self.created);
             half.state.set_idle();
             Poll::Ready(result)
         } else if half.state.schedule() {
+            // Keeps the pool alive while the operation is started.
+            let _pool = match self.pool.upgrade_live() {
+                Ok(pool) => pool,
+                Err(err) => {
+                    half.state.set_idle();
+                    return Poll::Ready(Err(err));
+                }
+            };
+
+            #[cfg(feature = "tracing")]
+            tracing::trace!(
+                handle = ?self.handle,
+                direction = self.direction(half),
+                len = (*buf).len,
+                "io scheduled"
+            );
+
             StartThreadpoolIo(self.ptp_io);
             match (half.schedule)(
                 self.handle,
@@ -123,6 +169,24 @@ impl IoHandle {
                 buf,
             ) {
                 Poll::Ready(result) => {
+                    #[cfg(feature = "tracing")]
+                    match &result {
+                        Ok(transferred) => tracing::trace!(
+                            handle = ?self.handle,
+                            direction = self.direction(half),
+                            transferred,
+                            "io completed inline"
+                        ),
+                        Err(err) => tracing::trace!(
+                            handle = ?self.handle,
+                            direction = self.direction(half),
+                            os_error = err.raw_os_error(),
+                            error = %err,
+                            "io failed inline"
+                        ),
+                    }
+
+                    half.stats.record(&result, self.created);
                     half.waker.register(cx.waker());
                     half.state.set_idle();
                     Poll::Ready(result)
@@ -159,6 +223,14 @@ impl IoHandle {
             return Ok(());
         }
         let cancel = half.state.cancel(wait);
+        half.stats.cancelled();
+        #[cfg(feature = "tracing")]
+        tracing::trace!(
+            handle = ?self.handle,
+            direction = self.direction(half),
+            wait,
+            "io cancelled"
+        );
 
         if wait {
             Handle::try_current().map(|h| h.may_block());
@@ -185,6 +257,43 @@ impl IoHandle {
     pub(crate) unsafe fn cancel_write(&self, wait: bool) -> io::Result<()> {
         self.cancel(&self.write, wait)
     }
+
+    #[cfg(feature = "tracing")]
+    fn direction(&self, half: &IoHalf) -> &'static str {
+        if ptr::eq(half, &*self.read) {
+            "read"
+        } else {
+            "write"
+        }
+    }
+
+    pub(crate) fn is_reading(&self) -> bool {
+        self.read.state.is_busy()
+    }
+
+    pub(crate) fn is_writing(&self) -> bool {
+        self.write.state.is_busy()
+    }
+
+    pub(crate) fn is_busy(&self) -> bool {
+        self.is_reading() || self.is_writing()
+    }
+
+    pub(crate) fn is_pending(&self) -> bool {
+        self.read.state.is_pending() || self.write.state.is_pending()
+    }
+
+    pub(crate) fn read_stats(&self) -> &IoStats {
+        &self.read.stats
+    }
+
+    pub(crate) fn write_stats(&self) -> &IoStats {
+        &self.write.stats
+    }
+
+    pub(crate) fn created(&self) -> Instant {
+        self.created
+    }
 }
 
 impl IoHalf {
@@ -193,6 +302,7 @@ impl IoHalf {
             state: IoState::new(),
             result: IoResult::new(),
             waker: AtomicWaker::new(),
+            stats: IoStats::new(),
             overlapped: Default::default(),
             schedule,
             cancel,
@@ -206,7 +316,9 @@ impl Drop for IoHandle {
             self.cancel_read(true).unwrap();
             self.cancel_write(true).unwrap();
 
-            if !self.ptp_io.is_null() {
+            // Once the pool is gone its cleanup group has closed the IO object already, otherwise
+            // it's kept alive until the object is closed.
+            if let Some(_pool) = self.pool.upgrade().filter(|_| !self.ptp_io.is_null()) {
                 CloseThreadpoolIo(self.ptp_io);
             }
             (self.close)(self.handle);
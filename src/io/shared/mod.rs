@@ -2,8 +2,10 @@ mod event;
 mod handle;
 mod result;
 mod state;
+mod stats;
 
 pub(crate) use event::IoEvent;
 pub(crate) use handle::IoHandle;
 pub(crate) use result::IoResult;
 pub(crate) use state::IoState;
+pub(crate) use stats::IoStats;
@@ -101,6 +101,14 @@ impl IoState {
         self.0.load(Ordering::Relaxed) == Self::PENDING
     }
 
+    /// Whether an operation was started and hasn't completed or been cancelled yet.
+    pub(crate) fn is_pending(&self) -> bool {
+        matches!(
+            self.0.load(Ordering::Acquire),
+            Self::SCHEDULING | Self::PENDING | Self::CALLBACK
+        )
+    }
+
     pub(crate) fn is_busy(&self) -> bool {
         !matches!(self.0.load(Ordering::Relaxed), Self::IDLE | Self::READY)
     }
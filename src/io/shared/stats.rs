@@ -0,0 +1,76 @@
+use std::{
+    io,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+pub(crate) struct IoStats {
+    bytes: AtomicU64,
+    ops: AtomicU64,
+    errors: AtomicU64,
+    cancellations: AtomicU64,
+    last_activity: AtomicU64,
+}
+
+impl IoStats {
+    pub(crate) const fn new() -> Self {
+        Self {
+            bytes: AtomicU64::new(0),
+            ops: AtomicU64::new(0),
+            errors: AtomicU64::new(0),
+            cancellations: AtomicU64::new(0),
+            last_activity: AtomicU64::new(0),
+        }
+    }
+
+    pub(crate) fn record(&self, result: &io::Result<usize>, epoch: Instant) {
+        match result {
+            Ok(transferred) => self.succeeded(*transferred, epoch),
+            Err(_) => self.failed(epoch),
+        }
+    }
+
+    pub(crate) fn succeeded(&self, transferred: usize, epoch: Instant) {
+        self.bytes.fetch_add(transferred as u64, Ordering::Relaxed);
+        self.ops.fetch_add(1, Ordering::Relaxed);
+        self.touch(epoch);
+    }
+
+    pub(crate) fn failed(&self, epoch: Instant) {
+        self.errors.fetch_add(1, Ordering::Relaxed);
+        self.touch(epoch);
+    }
+
+    pub(crate) fn cancelled(&self) {
+        self.cancellations.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records activity as nanoseconds since `epoch`, never zero so it can be told apart from "never".
+    fn touch(&self, epoch: Instant) {
+        let now = (epoch.elapsed().as_nanos() as u64).max(1);
+        self.last_activity.store(now, Ordering::Relaxed);
+    }
+
+    pub(crate) fn bytes(&self) -> u64 {
+        self.bytes.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn ops(&self) -> u64 {
+        self.ops.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn errors(&self) -> u64 {
+        self.errors.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn cancellations(&self) -> u64 {
+        self.cancellations.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn last_activity(&self, epoch: Instant) -> Option<Instant> {
+        match self.last_activity.load(Ordering::Relaxed) {
+            0 => None,
+            nanos => Some(epoch + Duration::from_nanos(nanos)),
+        }
+    }
+}
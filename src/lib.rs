@@ -8,10 +8,16 @@ pub mod io;
 pub mod net;
 pub mod task;
 pub mod threadpool;
+#[cfg(feature = "time")]
+pub mod time;
 
+#[cfg(feature = "console")]
+pub(crate) mod console;
 pub(crate) mod context;
 pub(crate) mod util;
 
+#[cfg(feature = "global")]
+pub use crate::context::set_global_default;
 pub use crate::{context::current as context, task::spawn, threadpool::Threadpool};
 
 #[cfg(feature = "macros")]
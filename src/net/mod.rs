@@ -1,3 +1,5 @@
+#[cfg(feature = "sim")]
+pub mod sim;
 mod sockaddr;
 pub mod tcp;
 
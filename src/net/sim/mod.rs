@@ -0,0 +1,448 @@
+//! An in-process network for testing TCP code deterministically.
+//!
+//! A pool built with [`Builder::network`] resolves names, binds listeners and connects streams
+//! against its [`Network`] instead of the OS. Data only moves when the pool's clock says so, which
+//! together with [`Builder::deterministic`] and [`Builder::start_paused`] makes every run with a
+//! given seed behave the same.
+//!
+//! [`Builder::network`]: crate::threadpool::Builder::network
+//! [`Builder::deterministic`]: crate::threadpool::Builder::deterministic
+//! [`Builder::start_paused`]: crate::threadpool::Builder::start_paused
+
+mod socket;
+
+use std::{
+    cell::Cell,
+    collections::HashMap,
+    fmt,
+    future::Future,
+    io,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    pin::Pin,
+    sync::{Arc, Mutex, Weak},
+    task::{Context, Poll, Waker},
+    time::Duration,
+};
+
+use pin_project_lite::pin_project;
+
+use socket::Pipe;
+pub(crate) use socket::{Listener, Stream};
+
+use crate::{task::JoinHandle, threadpool::Handle};
+
+/// A virtual network of named hosts, shared by the pools it is given to.
+#[derive(Clone)]
+pub struct Network {
+    state: Arc<Mutex<State>>,
+}
+
+/// A host of a [`Network`], see [`Network::host`].
+#[derive(Clone)]
+pub struct Host {
+    network: Network,
+    name: Arc<str>,
+    ip: IpAddr,
+}
+
+pin_project! {
+    /// Runs a future on a [`Host`], see [`Host::run`].
+    pub struct OnHost<F> {
+        #[pin]
+        future: F,
+        ip: IpAddr,
+    }
+}
+
+struct State {
+    hosts: HashMap<Arc<str>, IpAddr>,
+    latency: Duration,
+    links: HashMap<(IpAddr, IpAddr), Link>,
+    listeners: HashMap<SocketAddr, Weak<Listener>>,
+    next_port: u16,
+    pipes: Vec<Weak<Pipe>>,
+    /// Connections waiting for a partition to heal, each registered once under its own id.
+    waiters: HashMap<u64, Waker>,
+    next_waiter: u64,
+}
+
+/// Removes the waker a [`Network::reach`] call registered once it's done.
+struct Waiter<'a> {
+    network: &'a Network,
+    id: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Link {
+    latency: Option<Duration>,
+    partitioned: bool,
+    failed: bool,
+}
+
+thread_local! {
+    static HOST: Cell<Option<IpAddr>> = const { Cell::new(None) };
+}
+
+/// The host code runs on outside of [`Host::run`].
+const LOCALHOST: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+const FIRST_PORT: u16 = 49152;
+
+impl Network {
+    /// Creates a network with a single `localhost` host at `127.0.0.1` and no latency.
+    pub fn new() -> Self {
+        let mut hosts = HashMap::new();
+        hosts.insert(Arc::from("localhost"), LOCALHOST);
+        Self {
+            state: Arc::new(Mutex::new(State {
+                hosts,
+                latency: Duration::ZERO,
+                links: HashMap::new(),
+                listeners: HashMap::new(),
+                next_port: FIRST_PORT,
+                pipes: Vec::new(),
+                waiters: HashMap::new(),
+                next_waiter: 0,
+            })),
+        }
+    }
+
+    /// Returns the host named `name`, adding it with the next `10.0.0.0/8` address if needed.
+    pub fn host(&self, name: &str) -> Host {
+        let mut state = self.state.lock().unwrap();
+        let next = state.hosts.len() as u32;
+        let (name, ip) = match state.hosts.get_key_value(name) {
+            Some((name, ip)) => (name.clone(), *ip),
+            None => {
+                let name = Arc::<str>::from(name);
+                let ip = IpAddr::V4(Ipv4Addr::from(0x0a00_0000 + next));
+                state.hosts.insert(name.clone(), ip);
+                (name, ip)
+            }
+        };
+        Host {
+            network: self.clone(),
+            name,
+            ip,
+        }
+    }
+
+    /// Sets the one-way latency of every link without one of its own.
+    pub fn set_latency(&self, latency: Duration) {
+        self.state.lock().unwrap().latency = latency;
+    }
+
+    /// Sets the one-way latency between `a` and `b`.
+    pub fn set_link_latency(&self, a: &Host, b: &Host, latency: Duration) {
+        self.update_link(a, b, |link| link.latency = Some(latency));
+    }
+
+    /// Stops traffic between `a` and `b` until [`Network::heal`], without breaking connections.
+    ///
+    /// Data sent meanwhile is held back and delivered once the partition heals, and new
+    /// connections wait for it as well.
+    pub fn partition(&self, a: &Host, b: &Host) {
+        self.update_link(a, b, |link| link.partitioned = true);
+    }
+
+    pub fn heal(&self, a: &Host, b: &Host) {
+        self.update_link(a, b, |link| link.partitioned = false);
+    }
+
+    /// Takes the link between `a` and `b` down until [`Network::restore_link`].
+    ///
+    /// Connections between them fail with [`io::ErrorKind::ConnectionReset`] and new ones with
+    /// [`io::ErrorKind::HostUnreachable`].
+    pub fn fail_link(&self, a: &Host, b: &Host) {
+        self.update_link(a, b, |link| link.failed = true);
+    }
+
+    pub fn restore_link(&self, a: &Host, b: &Host) {
+        self.update_link(a, b, |link| link.failed = false);
+    }
+
+    fn update_link(&self, a: &Host, b: &Host, f: impl FnOnce(&mut Link)) {
+        let mut state = self.state.lock().unwrap();
+        let link = state.links.entry(key(a.ip, b.ip)).or_default();
+        f(link);
+        let failed = link.failed;
+
+        let mut pipes = Vec::new();
+        state.pipes.retain(|pipe| match pipe.upgrade() {
+            Some(pipe) => {
+                if key(pipe.from, pipe.to) == key(a.ip, b.ip) {
+                    pipes.push(pipe);
+                }
+                true
+            }
+            None => false,
+        });
+        let waiters = std::mem::take(&mut state.waiters);
+        drop(state);
+
+        for pipe in pipes {
+            if failed {
+                pipe.reset();
+            }
+            pipe.wake();
+        }
+        waiters.into_values().for_each(Waker::wake);
+    }
+
+    pub(crate) fn resolve(&self, name: &str) -> Option<IpAddr> {
+        self.state.lock().unwrap().hosts.get(name).copied()
+    }
+
+    /// The one-way latency from `from` to `to`, or `None` while they're partitioned.
+    pub(crate) fn link(&self, from: IpAddr, to: IpAddr) -> io::Result<Option<Duration>> {
+        self.state.lock().unwrap().link(from, to)
+    }
+
+    /// Waits until `to` can be reached from `from` and returns the latency between them.
+    pub(crate) async fn reach(&self, from: IpAddr, to: IpAddr) -> io::Result<Duration> {
+        if !self
+            .state
+            .lock()
+            .unwrap()
+            .hosts
+            .values()
+            .any(|&ip| ip == to)
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::HostUnreachable,
+                "no such simulated host",
+            ));
+        }
+        let mut waiter = Waiter {
+            network: self,
+            id: None,
+        };
+        std::future::poll_fn(|cx| {
+            let mut state = self.state.lock().unwrap();
+            match state.link(from, to)? {
+                Some(latency) => Poll::Ready(Ok(latency)),
+                None => {
+                    state.wait(&mut waiter.id, cx.waker());
+                    Poll::Pending
+                }
+            }
+        })
+        .await
+    }
+
+    /// Maps loopback and unspecified addresses to the current host.
+    pub(crate) fn local(&self, addr: SocketAddr) -> SocketAddr {
+        if addr.ip().is_loopback() || addr.ip().is_unspecified() {
+            SocketAddr::new(current_host(), addr.port())
+        } else {
+            addr
+        }
+    }
+
+    pub(crate) fn bind(&self, addr: SocketAddr) -> io::Result<Arc<Listener>> {
+        let mut addr = self.local(addr);
+        let mut state = self.state.lock().unwrap();
+        if !state.hosts.values().any(|&ip| ip == addr.ip()) {
+            return Err(io::Error::new(
+                io::ErrorKind::AddrNotAvailable,
+                "the address doesn't belong to a simulated host",
+            ));
+        }
+        if addr.port() == 0 {
+            addr.set_port(state.next_port());
+        }
+        if let Some(listener) = state.listeners.get(&addr) {
+            if listener.strong_count() > 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::AddrInUse,
+                    "the address is already in use",
+                ));
+            }
+        }
+
+        let listener = Arc::new(Listener::new(self.clone(), addr));
+        state.listeners.insert(addr, Arc::downgrade(&listener));
+        Ok(listener)
+    }
+
+    pub(crate) fn listener(&self, addr: SocketAddr) -> Option<Arc<Listener>> {
+        let state = self.state.lock().unwrap();
+        state.listeners.get(&addr).and_then(Weak::upgrade)
+    }
+
+    pub(crate) fn unbind(&self, addr: SocketAddr) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(listener) = state.listeners.get(&addr) {
+            if listener.strong_count() == 0 {
+                state.listeners.remove(&addr);
+            }
+        }
+    }
+
+    pub(crate) fn port(&self) -> u16 {
+        self.state.lock().unwrap().next_port()
+    }
+
+    pub(crate) fn pipe(&self, from: IpAddr, to: IpAddr) -> Arc<Pipe> {
+        let pipe = Arc::new(Pipe::new(from, to));
+        self.state.lock().unwrap().pipes.push(Arc::downgrade(&pipe));
+        pipe
+    }
+}
+
+impl State {
+    fn link(&self, from: IpAddr, to: IpAddr) -> io::Result<Option<Duration>> {
+        if from == to {
+            return Ok(Some(Duration::ZERO));
+        }
+        let link = self.links.get(&key(from, to)).copied().unwrap_or_default();
+        if link.failed {
+            Err(io::Error::new(
+                io::ErrorKind::HostUnreachable,
+                "the simulated link is down",
+            ))
+        } else if link.partitioned {
+            Ok(None)
+        } else {
+            Ok(Some(link.latency.unwrap_or(self.latency)))
+        }
+    }
+
+    fn next_port(&mut self) -> u16 {
+        let port = self.next_port;
+        self.next_port = self.next_port.checked_add(1).unwrap_or(FIRST_PORT);
+        port
+    }
+
+    /// Registers `waker` under `id`, or under a new id if it isn't registered anymore.
+    fn wait(&mut self, id: &mut Option<u64>, waker: &Waker) {
+        if let Some(registered) = id.and_then(|id| self.waiters.get_mut(&id)) {
+            if !registered.will_wake(waker) {
+                *registered = waker.clone();
+            }
+            return;
+        }
+        let next = self.next_waiter;
+        self.next_waiter += 1;
+        self.waiters.insert(next, waker.clone());
+        *id = Some(next);
+    }
+}
+
+impl Drop for Waiter<'_> {
+    fn drop(&mut self) {
+        if let Some(id) = self.id {
+            self.network.state.lock().unwrap().waiters.remove(&id);
+        }
+    }
+}
+
+impl Host {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn ip(&self) -> IpAddr {
+        self.ip
+    }
+
+    pub fn network(&self) -> &Network {
+        &self.network
+    }
+
+    /// Makes sockets created and connections made while polling `future` come from this host.
+    ///
+    /// Tasks spawned by `future` run on the default `localhost` host unless they're wrapped as
+    /// well, see [`Host::spawn`].
+    pub fn run<F: Future>(&self, future: F) -> OnHost<F> {
+        OnHost {
+            future,
+            ip: self.ip,
+        }
+    }
+
+    /// Spawns `future` on the current pool, running it on this host.
+    #[track_caller]
+    pub fn spawn<F, T>(&self, future: F) -> JoinHandle<T>
+    where
+        F: Future<Output = T> + Send + 'static,
+        T: Send + 'static,
+    {
+        crate::spawn(self.run(future))
+    }
+}
+
+impl<F: Future> Future for OnHost<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let previous = HOST.with(|host| host.replace(Some(*this.ip)));
+        let _restore = Restore(previous);
+        this.future.poll(cx)
+    }
+}
+
+struct Restore(Option<IpAddr>);
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        HOST.with(|host| host.set(self.0));
+    }
+}
+
+impl Default for Network {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for Network {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.state.lock().unwrap();
+        f.debug_struct("Network")
+            .field("hosts", &state.hosts)
+            .field("latency", &state.latency)
+            .finish()
+    }
+}
+
+impl fmt::Debug for Host {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Host")
+            .field("name", &self.name)
+            .field("ip", &self.ip)
+            .finish()
+    }
+}
+
+impl<F> fmt::Debug for OnHost<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OnHost").field("ip", &self.ip).finish()
+    }
+}
+
+/// Returns the network of the current pool, if it has one.
+#[track_caller]
+pub(crate) fn current() -> Option<Network> {
+    Handle::current().network().cloned()
+}
+
+pub(crate) fn unsupported() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        "simulated sockets don't support this option",
+    )
+}
+
+/// The host of the code being polled, `localhost` outside of [`Host::run`].
+pub(crate) fn current_host() -> IpAddr {
+    HOST.with(Cell::get).unwrap_or(LOCALHOST)
+}
+
+fn key(a: IpAddr, b: IpAddr) -> (IpAddr, IpAddr) {
+    if a <= b {
+        (a, b)
+    } else {
+        (b, a)
+    }
+}
@@ -0,0 +1,406 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    future::Future,
+    io,
+    net::{IpAddr, Shutdown, SocketAddr},
+    pin::Pin,
+    ptr,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+};
+
+use super::{current_host, Network};
+use crate::{
+    io::shared::IoStats,
+    threadpool::{Handle, WeakHandle},
+    time::{Instant, Sleep},
+};
+
+/// One direction of a simulated connection.
+pub(crate) struct Pipe {
+    pub(super) from: IpAddr,
+    pub(super) to: IpAddr,
+    state: Mutex<PipeState>,
+}
+
+struct PipeState {
+    /// Data in flight, ordered by arrival time.
+    segments: VecDeque<Segment>,
+    /// Data that arrived but hasn't been read yet.
+    received: VecDeque<u8>,
+    /// The writer shut down and it reached the reader.
+    eof: bool,
+    /// The writer shut down.
+    closed: bool,
+    reset: bool,
+    reader: Option<Waker>,
+}
+
+struct Segment {
+    arrival: Instant,
+    data: Vec<u8>,
+    eof: bool,
+}
+
+/// One end of a simulated connection.
+pub(crate) struct Stream {
+    local: SocketAddr,
+    peer: SocketAddr,
+    network: Network,
+    pool: WeakHandle,
+    incoming: Arc<Pipe>,
+    outgoing: Arc<Pipe>,
+    /// Wakes up a pending read when the next segment arrives.
+    timer: Mutex<Option<Sleep>>,
+    read_stats: IoStats,
+    write_stats: IoStats,
+    created: std::time::Instant,
+}
+
+pub(crate) struct Listener {
+    addr: SocketAddr,
+    network: Network,
+    state: Mutex<ListenerState>,
+}
+
+struct ListenerState {
+    backlog: VecDeque<Arc<Stream>>,
+    /// Pending accepts, all woken up when a connection comes in.
+    waiters: HashMap<u64, Waker>,
+    next_waiter: u64,
+}
+
+impl Pipe {
+    pub(super) fn new(from: IpAddr, to: IpAddr) -> Self {
+        Self {
+            from,
+            to,
+            state: Mutex::new(PipeState {
+                segments: VecDeque::new(),
+                received: VecDeque::new(),
+                eof: false,
+                closed: false,
+                reset: false,
+                reader: None,
+            }),
+        }
+    }
+
+    pub(super) fn reset(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.reset = true;
+        state.segments.clear();
+        state.received.clear();
+    }
+
+    pub(super) fn wake(&self) {
+        let reader = self.state.lock().unwrap().reader.take();
+        if let Some(reader) = reader {
+            reader.wake();
+        }
+    }
+
+    fn send(
+        &self,
+        now: Instant,
+        latency: io::Result<Option<std::time::Duration>>,
+        data: &[u8],
+        eof: bool,
+    ) -> io::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        if state.reset {
+            return Err(io::ErrorKind::ConnectionReset.into());
+        }
+        if state.closed {
+            return Err(io::Error::new(
+                io::ErrorKind::BrokenPipe,
+                "the stream was shut down for writing",
+            ));
+        }
+        let latency = match latency {
+            Ok(latency) => latency.unwrap_or_default(),
+            Err(_) => {
+                drop(state);
+                self.reset();
+                return Err(io::ErrorKind::ConnectionReset.into());
+            }
+        };
+
+        // Segments never overtake each other, like on a TCP connection.
+        let arrival = state
+            .segments
+            .back()
+            .map_or(now + latency, |last| last.arrival.max(now + latency));
+        state.segments.push_back(Segment {
+            arrival,
+            data: data.to_vec(),
+            eof,
+        });
+        state.closed = eof;
+        let reader = state.reader.take();
+        drop(state);
+
+        if let Some(reader) = reader {
+            reader.wake();
+        }
+        Ok(())
+    }
+}
+
+impl Stream {
+    /// Creates both ends of a connection from `client` to `server`.
+    fn pair(
+        network: &Network,
+        pool: &Handle,
+        client: SocketAddr,
+        server: SocketAddr,
+    ) -> (Arc<Self>, Arc<Self>) {
+        let upstream = network.pipe(client.ip(), server.ip());
+        let downstream = network.pipe(server.ip(), client.ip());
+        let end = |local, peer, incoming: &Arc<Pipe>, outgoing: &Arc<Pipe>| {
+            Arc::new(Stream {
+                local,
+                peer,
+                network: network.clone(),
+                pool: pool.downgrade(),
+                incoming: incoming.clone(),
+                outgoing: outgoing.clone(),
+                timer: Mutex::new(None),
+                read_stats: IoStats::new(),
+                write_stats: IoStats::new(),
+                created: std::time::Instant::now(),
+            })
+        };
+        (
+            end(client, server, &downstream, &upstream),
+            end(server, client, &upstream, &downstream),
+        )
+    }
+
+    /// Connects to the listener at `addr` from the current host.
+    pub(crate) async fn connect(network: &Network, addr: SocketAddr) -> io::Result<Arc<Self>> {
+        let pool = Handle::current();
+        let local = current_host();
+        let addr = network.local(addr);
+
+        // The handshake takes a round trip.
+        let latency = network.reach(local, addr.ip()).await?;
+        crate::time::sleep(latency).await;
+        let listener = network
+            .listener(addr)
+            .ok_or(io::ErrorKind::ConnectionRefused)?;
+        let local = SocketAddr::new(local, network.port());
+        let (client, server) = Stream::pair(network, &pool, local, addr);
+        listener.push(server);
+        crate::time::sleep(latency).await;
+        Ok(client)
+    }
+
+    pub(crate) fn local_addr(&self) -> SocketAddr {
+        self.local
+    }
+
+    pub(crate) fn peer_addr(&self) -> SocketAddr {
+        self.peer
+    }
+
+    /// Copies up to `len` bytes that arrived by now into `buf`.
+    ///
+    /// # Safety
+    /// `buf` must be valid for writes of `len` bytes.
+    pub(crate) unsafe fn poll_read(
+        &self,
+        cx: &mut Context<'_>,
+        buf: *mut u8,
+        len: usize,
+    ) -> Poll<io::Result<usize>> {
+        let poll = self.poll_read_inner(cx, buf, len);
+        if let Poll::Ready(result) = &poll {
+            // The sleep holds on to the pool, which only pending reads may keep alive.
+            self.timer.lock().unwrap().take();
+            self.read_stats.record(result, self.created);
+        }
+        poll
+    }
+
+    unsafe fn poll_read_inner(
+        &self,
+        cx: &mut Context<'_>,
+        buf: *mut u8,
+        len: usize,
+    ) -> Poll<io::Result<usize>> {
+        let pool = self.pool.upgrade_live()?;
+        let pipe = &self.incoming;
+        loop {
+            let now = pool.clock().now();
+            let reachable = self
+                .network
+                .link(pipe.from, pipe.to)
+                .ok()
+                .flatten()
+                .is_some();
+
+            let mut state = pipe.state.lock().unwrap();
+            if state.reset {
+                return Poll::Ready(Err(io::ErrorKind::ConnectionReset.into()));
+            }
+            while reachable && state.segments.front().is_some_and(|s| s.arrival <= now) {
+                let segment = state.segments.pop_front().unwrap();
+                state.received.extend(segment.data);
+                state.eof |= segment.eof;
+            }
+
+            if !state.received.is_empty() || len == 0 {
+                let n = len.min(state.received.len());
+                for (i, byte) in state.received.drain(..n).enumerate() {
+                    ptr::write(buf.add(i), byte);
+                }
+                return Poll::Ready(Ok(n));
+            }
+            if state.eof {
+                return Poll::Ready(Ok(0));
+            }
+
+            state.reader = Some(cx.waker().clone());
+            let arrival = match state.segments.front() {
+                Some(segment) if reachable => segment.arrival,
+                // Woken up by a write or a link update.
+                _ => return Poll::Pending,
+            };
+            drop(state);
+
+            let mut timer = self.timer.lock().unwrap();
+            let sleep = match &mut *timer {
+                Some(sleep) if sleep.deadline() == arrival => sleep,
+                timer => timer.insert(Sleep::new(pool.clone(), arrival)),
+            };
+            if Pin::new(sleep).poll(cx).is_pending() {
+                return Poll::Pending;
+            }
+        }
+    }
+
+    pub(crate) fn poll_write(&self, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let result = self.send(buf, false).map(|()| buf.len());
+        self.write_stats.record(&result, self.created);
+        Poll::Ready(result)
+    }
+
+    fn send(&self, data: &[u8], eof: bool) -> io::Result<()> {
+        let pool = self.pool.upgrade_live()?;
+        let pipe = &self.outgoing;
+        let latency = self.network.link(pipe.from, pipe.to);
+        pipe.send(pool.clock().now(), latency, data, eof)
+    }
+
+    pub(crate) fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        match how {
+            Shutdown::Read => Ok(()),
+            Shutdown::Write | Shutdown::Both => match self.send(&[], true) {
+                // Shutting down twice is fine.
+                Err(err) if err.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+                result => result,
+            },
+        }
+    }
+
+    pub(crate) fn cancel_read(&self) {
+        self.timer.lock().unwrap().take();
+        self.incoming.state.lock().unwrap().reader = None;
+        self.read_stats.cancelled();
+    }
+
+    pub(crate) fn read_stats(&self) -> &IoStats {
+        &self.read_stats
+    }
+
+    pub(crate) fn write_stats(&self) -> &IoStats {
+        &self.write_stats
+    }
+
+    pub(crate) fn created(&self) -> std::time::Instant {
+        self.created
+    }
+}
+
+impl Drop for Stream {
+    fn drop(&mut self) {
+        let _ = self.shutdown(Shutdown::Write);
+        // Nobody is left to read, so the peer's writes fail from now on.
+        self.incoming.reset();
+    }
+}
+
+impl Listener {
+    pub(super) fn new(network: Network, addr: SocketAddr) -> Self {
+        Self {
+            addr,
+            network,
+            state: Mutex::new(ListenerState {
+                backlog: VecDeque::new(),
+                waiters: HashMap::new(),
+                next_waiter: 0,
+            }),
+        }
+    }
+
+    pub(crate) fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    fn push(&self, stream: Arc<Stream>) {
+        let mut state = self.state.lock().unwrap();
+        state.backlog.push_back(stream);
+        let waiters = std::mem::take(&mut state.waiters);
+        drop(state);
+
+        waiters.into_values().for_each(Waker::wake);
+    }
+
+    /// Accepts the next connection, registering the waker under `id` until there is one.
+    pub(crate) fn poll_accept(
+        &self,
+        cx: &mut Context<'_>,
+        id: &mut Option<u64>,
+    ) -> Poll<io::Result<Arc<Stream>>> {
+        let mut state = self.state.lock().unwrap();
+        match state.backlog.pop_front() {
+            Some(stream) => {
+                if let Some(id) = id.take() {
+                    state.waiters.remove(&id);
+                }
+                Poll::Ready(Ok(stream))
+            }
+            None => {
+                state.wait(id, cx.waker());
+                Poll::Pending
+            }
+        }
+    }
+
+    /// Removes the waker of an accept that is dropped before completing.
+    pub(crate) fn unregister(&self, id: u64) {
+        self.state.lock().unwrap().waiters.remove(&id);
+    }
+}
+
+impl ListenerState {
+    fn wait(&mut self, id: &mut Option<u64>, waker: &Waker) {
+        if let Some(registered) = id.and_then(|id| self.waiters.get_mut(&id)) {
+            if !registered.will_wake(waker) {
+                *registered = waker.clone();
+            }
+            return;
+        }
+        let next = self.next_waiter;
+        self.next_waiter += 1;
+        self.waiters.insert(next, waker.clone());
+        *id = Some(next);
+    }
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        self.network.unbind(self.addr);
+    }
+}
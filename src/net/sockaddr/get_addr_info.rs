@@ -10,21 +10,17 @@ use std::{
 };
 use winapi::{
     shared::ws2def::{ADDRINFOEXW, AF_UNSPEC, NS_ALL},
-    um::{
-        winnt::TP_CALLBACK_ENVIRON_V3,
-        ws2tcpip::{FreeAddrInfoExW, GetAddrInfoExW},
-    },
+    um::ws2tcpip::{FreeAddrInfoExW, GetAddrInfoExW},
 };
 
 use socket2::SockAddr;
 
-use crate::io::shared::IoEvent;
+use crate::{io::shared::IoEvent, threadpool::Handle};
+
+pub(super) fn get_addr_info(host: &str, port: Option<u16>, pool: &Handle) -> GetAddrInfoFuture {
+    #[cfg(feature = "tracing")]
+    tracing::trace!(host, ?port, "resolving");
 
-pub(super) fn get_addr_info(
-    host: &str,
-    port: Option<u16>,
-    callback_environ: &TP_CALLBACK_ENVIRON_V3,
-) -> GetAddrInfoFuture {
     let (host, port) = match port {
         Some(p) => (host, Some(to_wstr(&p.to_string()))),
         None => {
@@ -43,7 +39,7 @@ pub(super) fn get_addr_info(
             ..Default::default()
         },
     });
-    let event = IoEvent::new(callback_environ);
+    let event = IoEvent::new(pool);
 
     GetAddrInfoFuture {
         host,
@@ -113,11 +109,34 @@ impl Future for GetAddrInfoFuture {
                 });
 
                 match poll {
-                    Poll::Ready(Ok(())) => Poll::Ready(Ok(GetAddrInfoIter {
-                        addrinfo: self.inner.result,
-                        current: self.inner.result,
-                    })),
-                    Poll::Ready(Err(err)) => Poll::Ready(Err(err)),
+                    Poll::Ready(Ok(())) => {
+                        let iter = GetAddrInfoIter {
+                            addrinfo: self.inner.result,
+                            current: self.inner.result,
+                        };
+                        // A null `addrinfo` makes the iterator borrow the list instead of freeing it
+                        #[cfg(feature = "tracing")]
+                        tracing::debug!(
+                            host = %self.host_lossy(),
+                            addrs = ?GetAddrInfoIter {
+                                addrinfo: ptr::null_mut(),
+                                current: self.inner.result,
+                            }
+                            .collect::<Vec<_>>(),
+                            "resolved"
+                        );
+                        Poll::Ready(Ok(iter))
+                    }
+                    Poll::Ready(Err(err)) => {
+                        #[cfg(feature = "tracing")]
+                        tracing::debug!(
+                            host = %self.host_lossy(),
+                            os_error = err.raw_os_error(),
+                            error = %err,
+                            "resolution failed"
+                        );
+                        Poll::Ready(Err(err))
+                    }
                     Poll::Pending => Poll::Pending,
                 }
             }
@@ -128,6 +147,13 @@ impl Future for GetAddrInfoFuture {
     }
 }
 
+#[cfg(feature = "tracing")]
+impl GetAddrInfoFuture {
+    fn host_lossy(&self) -> String {
+        String::from_utf16_lossy(&self.host[..self.host.len() - 1])
+    }
+}
+
 pub(super) struct GetAddrInfoIter {
     addrinfo: *mut ADDRINFOEXW,
     current: *mut ADDRINFOEXW,
@@ -156,6 +182,8 @@ impl Iterator for GetAddrInfoIter {
 
 impl Drop for GetAddrInfoIter {
     fn drop(&mut self) {
-        unsafe { FreeAddrInfoExW(self.addrinfo) }
+        if !self.addrinfo.is_null() {
+            unsafe { FreeAddrInfoExW(self.addrinfo) }
+        }
     }
 }
@@ -48,9 +48,7 @@ impl ToSocketAddrs for str {
         sealed::ToSocketAddrs {
             inner: match self.parse() {
                 Ok(addr) => sealed::ToSocketAddrsInner::Immediate { addr },
-                Err(_) => sealed::ToSocketAddrsInner::Future {
-                    future: get_addr_info(self, None, &Handle::current().callback_environ()),
-                },
+                Err(_) => resolve(self, None),
             },
         }
     }
@@ -69,13 +67,7 @@ impl ToSocketAddrs for (&str, u16) {
                 Ok(ip) => sealed::ToSocketAddrsInner::Immediate {
                     addr: SocketAddr::new(ip, self.1),
                 },
-                Err(_) => sealed::ToSocketAddrsInner::Future {
-                    future: get_addr_info(
-                        self.0,
-                        Some(self.1),
-                        &Handle::current().callback_environ(),
-                    ),
-                },
+                Err(_) => resolve(self.0, Some(self.1)),
             },
         }
     }
@@ -88,18 +80,37 @@ impl ToSocketAddrs for (String, u16) {
                 Ok(ip) => sealed::ToSocketAddrsInner::Immediate {
                     addr: SocketAddr::new(ip, self.1),
                 },
-                Err(_) => sealed::ToSocketAddrsInner::Future {
-                    future: get_addr_info(
-                        &self.0,
-                        Some(self.1),
-                        &Handle::current().callback_environ(),
-                    ),
-                },
+                Err(_) => resolve(&self.0, Some(self.1)),
             },
         }
     }
 }
 
+/// Resolves `host` through DNS, or against the simulated network of the current pool if it has one.
+fn resolve(host: &str, port: Option<u16>) -> sealed::ToSocketAddrsInner<'static> {
+    let pool = Handle::current();
+    #[cfg(feature = "sim")]
+    if let Some(network) = pool.network() {
+        let (host, port) = match port {
+            Some(port) => (host, Some(port)),
+            None => match host.split_once(':') {
+                Some((host, port)) => (host, port.parse().ok()),
+                None => (host, None),
+            },
+        };
+        // Unknown hosts resolve to nothing, like a failed lookup.
+        return match (network.resolve(host), port) {
+            (Some(ip), Some(port)) => sealed::ToSocketAddrsInner::Immediate {
+                addr: SocketAddr::new(ip, port),
+            },
+            _ => sealed::ToSocketAddrsInner::Slice { addrs: &[] },
+        };
+    }
+    sealed::ToSocketAddrsInner::Future {
+        future: get_addr_info(host, port, &pool),
+    }
+}
+
 impl<T: ToSocketAddrs + ?Sized> ToSocketAddrs for &T {
     fn to_socket_addrs(&self) -> sealed::ToSocketAddrs<'_> {
         (&**self).to_socket_addrs()
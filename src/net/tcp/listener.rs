@@ -8,11 +8,12 @@ use std::{
     pin::Pin,
     ptr,
     task::{Context, Poll},
+    time::Instant,
 };
 use winapi::{
     shared::{
-        guiddef::GUID, minwindef::TRUE, ws2def::SIO_GET_EXTENSION_FUNCTION_POINTER,
-        ws2ipdef::SOCKADDR_IN6,
+        guiddef::GUID, minwindef::TRUE, winerror::WSAENOTSOCK,
+        ws2def::SIO_GET_EXTENSION_FUNCTION_POINTER, ws2ipdef::SOCKADDR_IN6,
     },
     um::{
         mswsock::{
@@ -25,15 +26,29 @@ use winapi::{
 
 use socket2::{SockAddr, Socket};
 
-use super::TcpStream;
+use super::{stream::Inner, TcpStream};
+#[cfg(feature = "sim")]
+use crate::net::sim;
 use crate::{
-    io::shared::{IoEvent, IoHandle},
+    io::shared::{IoEvent, IoHandle, IoStats},
     net::ToSocketAddrs,
     threadpool::Handle,
     util::Extract,
 };
 
 pub struct TcpListener {
+    inner: ListenerInner,
+    pub(super) stats: IoStats,
+    pub(super) created: Instant,
+}
+
+enum ListenerInner {
+    Os(OsListener),
+    #[cfg(feature = "sim")]
+    Sim(std::sync::Arc<sim::Listener>),
+}
+
+struct OsListener {
     socket: Socket,
     acceptex: <LPFN_ACCEPTEX as Extract>::Inner,
     gaesa: <LPFN_GETACCEPTEXSOCKADDRS as Extract>::Inner,
@@ -41,6 +56,15 @@ pub struct TcpListener {
 
 pub struct Accept<'a> {
     listener: &'a TcpListener,
+    /// Unused by simulated listeners.
+    os: Option<OsAccept>,
+    /// The id of the waker registered with a simulated listener.
+    #[cfg(feature = "sim")]
+    waiter: Option<u64>,
+}
+
+struct OsAccept {
+    /// Closed on drop unless it was handed off to an accepted stream.
     client: Result<SOCKET, i32>,
     event: Result<Box<IoEvent>, i32>,
     buf: Vec<u8>,
@@ -55,6 +79,11 @@ impl TcpListener {
     const ADDR_SPACE: usize = mem::size_of::<SOCKADDR_IN6>() + 16;
 
     pub async fn bind<A: ToSocketAddrs>(addr: A) -> io::Result<TcpListener> {
+        #[cfg(feature = "sim")]
+        if let Some(network) = sim::current() {
+            return Self::bind_sim(&network, addr).await;
+        }
+
         let socket = super::socket::new()?;
 
         let acceptex = unsafe {
@@ -134,19 +163,53 @@ impl TcpListener {
         }
 
         Ok(TcpListener {
-            socket: unsafe { Socket::from_raw_socket(socket as u64) },
-            acceptex,
-            gaesa,
+            inner: ListenerInner::Os(OsListener {
+                socket: unsafe { Socket::from_raw_socket(socket as u64) },
+                acceptex,
+                gaesa,
+            }),
+            stats: IoStats::new(),
+            created: Instant::now(),
+        })
+    }
+
+    #[cfg(feature = "sim")]
+    async fn bind_sim<A: ToSocketAddrs>(
+        network: &sim::Network,
+        addr: A,
+    ) -> io::Result<TcpListener> {
+        let mut result = Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "the provided address couldn't be resolved",
+        ));
+        for addr in addr.to_socket_addrs().await? {
+            result = network.bind(addr);
+            if result.is_ok() {
+                break;
+            }
+        }
+        result.map(|listener| TcpListener {
+            inner: ListenerInner::Sim(listener),
+            stats: IoStats::new(),
+            created: Instant::now(),
         })
     }
 
     pub fn accept(&self) -> Accept<'_> {
+        let os = match &self.inner {
+            ListenerInner::Os(_) => Some(OsAccept {
+                client: super::socket::new().map_err(|err| err.raw_os_error().unwrap()),
+                event: IoEvent::new(&Handle::current()).map_err(|err| err.raw_os_error().unwrap()),
+                buf: Vec::with_capacity(Self::ADDR_SPACE * 2),
+            }),
+            #[cfg(feature = "sim")]
+            ListenerInner::Sim(_) => None,
+        };
         Accept {
             listener: self,
-            client: super::socket::new().map_err(|err| err.raw_os_error().unwrap()),
-            event: IoEvent::new(&Handle::current().callback_environ())
-                .map_err(|err| err.raw_os_error().unwrap()),
-            buf: Vec::with_capacity(Self::ADDR_SPACE * 2),
+            os,
+            #[cfg(feature = "sim")]
+            waiter: None,
         }
     }
 
@@ -158,24 +221,55 @@ impl TcpListener {
     }
 
     pub fn local_addr(&self) -> io::Result<SocketAddr> {
-        self.socket.local_addr().map(|a| a.as_std().unwrap())
+        match &self.inner {
+            ListenerInner::Os(os) => os.socket.local_addr().map(|a| a.as_std().unwrap()),
+            #[cfg(feature = "sim")]
+            ListenerInner::Sim(listener) => Ok(listener.local_addr()),
+        }
     }
 
     pub fn ttl(&self) -> io::Result<u32> {
-        self.socket.ttl()
+        match &self.inner {
+            ListenerInner::Os(os) => os.socket.ttl(),
+            #[cfg(feature = "sim")]
+            ListenerInner::Sim(_) => Err(sim::unsupported()),
+        }
     }
 
     pub fn set_ttl(&self, ttl: u32) -> io::Result<()> {
-        self.socket.set_ttl(ttl)
+        match &self.inner {
+            ListenerInner::Os(os) => os.socket.set_ttl(ttl),
+            #[cfg(feature = "sim")]
+            ListenerInner::Sim(_) => Err(sim::unsupported()),
+        }
     }
 }
 
-impl Future for Accept<'_> {
-    type Output = io::Result<(TcpStream, SocketAddr)>;
+impl Accept<'_> {
+    fn poll_accept(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<(TcpStream, SocketAddr)>> {
+        match (&self.listener.inner, &mut self.os) {
+            (ListenerInner::Os(listener), Some(accept)) => accept.poll(listener, cx),
+            #[cfg(feature = "sim")]
+            (ListenerInner::Sim(listener), _) => {
+                listener.poll_accept(cx, &mut self.waiter).map_ok(|stream| {
+                    let peer = stream.peer_addr();
+                    let inner = Inner::Sim(stream);
+                    (TcpStream { inner }, peer)
+                })
+            }
+            (ListenerInner::Os(_), None) => unreachable!(),
+        }
+    }
+}
 
-    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        let socket = self.listener.socket.as_raw_socket() as SOCKET;
-        let acceptex = self.listener.acceptex;
+impl OsAccept {
+    fn poll(
+        &mut self,
+        listener: &OsListener,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<(TcpStream, SocketAddr)>> {
+        let socket = listener.socket.as_raw_socket() as SOCKET;
+        let acceptex = listener.acceptex;
         let client = self.client.map_err(io::Error::from_raw_os_error)?;
         let buf = self.buf.as_mut_ptr();
 
@@ -214,7 +308,7 @@ impl Future for Accept<'_> {
                 let mut addr = ptr::null_mut();
                 let mut addr_len = 0;
                 let sock_addr = unsafe {
-                    (self.listener.gaesa)(
+                    (listener.gaesa)(
                         buf as *mut c_void,
                         0,
                         TcpListener::ADDR_SPACE as u32,
@@ -234,16 +328,69 @@ impl Future for Accept<'_> {
                     super::socket::cancel,
                     super::write::schedule,
                     super::socket::cancel,
-                    &handle.callback_environ(),
+                    &handle,
                 )?;
-                Poll::Ready(Ok((TcpStream { inner }, sock_addr.as_std().unwrap())))
+                // The stream owns the client socket now.
+                self.client = Err(WSAENOTSOCK as i32);
+                let peer = sock_addr.as_std().unwrap();
+                #[cfg(feature = "tracing")]
+                tracing::debug!(listener = ?listener.socket, %peer, "accepted connection");
+                let inner = Inner::Os(inner);
+                Poll::Ready(Ok((TcpStream { inner }, peer)))
+            }
+            Poll::Ready(Err(err)) => {
+                #[cfg(feature = "tracing")]
+                tracing::debug!(
+                    listener = ?listener.socket,
+                    os_error = err.raw_os_error(),
+                    error = %err,
+                    "accept failed"
+                );
+                Poll::Ready(Err(err))
             }
-            Poll::Ready(Err(err)) => Poll::Ready(Err(err)),
             Poll::Pending => Poll::Pending,
         }
     }
 }
 
+impl Future for Accept<'_> {
+    type Output = io::Result<(TcpStream, SocketAddr)>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let poll = self.poll_accept(cx);
+        let listener = self.listener;
+        match &poll {
+            Poll::Ready(Ok(_)) => listener.stats.succeeded(0, listener.created),
+            Poll::Ready(Err(_)) => listener.stats.failed(listener.created),
+            Poll::Pending => (),
+        }
+        poll
+    }
+}
+
+impl Drop for Accept<'_> {
+    fn drop(&mut self) {
+        match (&self.listener.inner, &self.os) {
+            (ListenerInner::Os(listener), Some(accept)) => {
+                // The event and the buffer are in use until a pending accept is cancelled.
+                if let Ok(event) = &accept.event {
+                    event.cancel(listener.socket.as_raw_socket() as HANDLE);
+                }
+                if let Ok(client) = accept.client {
+                    unsafe { super::socket::close(client as HANDLE) };
+                }
+            }
+            #[cfg(feature = "sim")]
+            (ListenerInner::Sim(listener), _) => {
+                if let Some(id) = self.waiter {
+                    listener.unregister(id);
+                }
+            }
+            (ListenerInner::Os(_), None) => {}
+        }
+    }
+}
+
 #[cfg(feature = "stream")]
 impl futures_core::Stream for Incoming<'_> {
     type Item = io::Result<(TcpStream, SocketAddr)>;
@@ -264,7 +411,13 @@ impl futures_core::Stream for Incoming<'_> {
 impl fmt::Debug for TcpListener {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let mut dbg = f.debug_struct("TcpListener");
-        dbg.field("socket", &self.socket);
+        match &self.inner {
+            ListenerInner::Os(os) => {
+                dbg.field("socket", &os.socket);
+            }
+            #[cfg(feature = "sim")]
+            ListenerInner::Sim(_) => {}
+        }
 
         if let Ok(addr) = self.local_addr() {
             dbg.field("addr", &addr);
@@ -2,9 +2,13 @@ mod listener;
 mod read;
 mod socket;
 mod split;
+mod stats;
 mod stream;
 mod write;
 
-pub use listener::{Accept, Incoming, TcpListener};
+#[cfg(feature = "stream")]
+pub use listener::Incoming;
+pub use listener::{Accept, TcpListener};
 pub use split::{ReadHalf, WriteHalf};
+pub use stats::{TcpListenerStats, TcpStreamStats};
 pub use stream::TcpStream;
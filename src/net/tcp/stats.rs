@@ -0,0 +1,55 @@
+use std::time::Instant;
+
+use super::{TcpListener, TcpStream};
+
+#[derive(Debug, Clone, Default)]
+pub struct TcpStreamStats {
+    pub bytes_read: u64,
+    pub bytes_written: u64,
+    /// Number of successful reads, including the ones that reached the end of the stream.
+    pub reads: u64,
+    pub writes: u64,
+    pub read_errors: u64,
+    pub write_errors: u64,
+    pub cancellations: u64,
+    pub last_read: Option<Instant>,
+    pub last_write: Option<Instant>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TcpListenerStats {
+    pub accepted: u64,
+    pub failed: u64,
+    pub last_accept: Option<Instant>,
+}
+
+impl TcpStream {
+    /// Returns IO statistics for the underlying socket, shared with the halves it was split into.
+    pub fn stats(&self) -> TcpStreamStats {
+        let epoch = self.inner.created();
+        let read = self.inner.read_stats();
+        let write = self.inner.write_stats();
+
+        TcpStreamStats {
+            bytes_read: read.bytes(),
+            bytes_written: write.bytes(),
+            reads: read.ops(),
+            writes: write.ops(),
+            read_errors: read.errors(),
+            write_errors: write.errors(),
+            cancellations: read.cancellations() + write.cancellations(),
+            last_read: read.last_activity(epoch),
+            last_write: write.last_activity(epoch),
+        }
+    }
+}
+
+impl TcpListener {
+    pub fn stats(&self) -> TcpListenerStats {
+        TcpListenerStats {
+            accepted: self.stats.ops(),
+            failed: self.stats.errors(),
+            last_accept: self.stats.last_activity(self.created),
+        }
+    }
+}
@@ -15,7 +15,7 @@ use winapi::{
     shared::{
         guiddef::GUID,
         minwindef::TRUE,
-        ws2def::{AF_INET, SIO_GET_EXTENSION_FUNCTION_POINTER, SOCKADDR, SOCKADDR_IN},
+        ws2def::{AF_INET, SIO_GET_EXTENSION_FUNCTION_POINTER, SOCKADDR, SOCKADDR_IN, WSABUF},
         ws2ipdef::SOCKADDR_IN6,
     },
     um::{
@@ -27,15 +27,25 @@ use winapi::{
 
 use socket2::{SockAddr, Socket};
 
+#[cfg(feature = "sim")]
+use crate::net::sim;
 use crate::{
-    io::shared::{IoEvent, IoHandle},
+    io::shared::{IoEvent, IoHandle, IoStats},
     net::ToSocketAddrs,
     threadpool::Handle,
     util::Extract,
 };
 
 pub struct TcpStream {
-    pub(super) inner: Arc<IoHandle>,
+    pub(super) inner: Inner,
+}
+
+/// Either an OS socket or a stream of the pool's simulated network.
+#[derive(Clone)]
+pub(super) enum Inner {
+    Os(Arc<IoHandle>),
+    #[cfg(feature = "sim")]
+    Sim(Arc<sim::Stream>),
 }
 
 struct Connect<'a> {
@@ -48,14 +58,24 @@ struct Connect<'a> {
 
 impl TcpStream {
     #[inline]
-    fn with_socket<T>(&self, f: impl FnOnce(&Socket) -> T) -> T {
-        let socket = unsafe { Socket::from_raw_socket(self.inner.handle as u64) };
+    fn with_socket<T>(&self, f: impl FnOnce(&Socket) -> io::Result<T>) -> io::Result<T> {
+        let handle = match &self.inner {
+            Inner::Os(handle) => handle,
+            #[cfg(feature = "sim")]
+            Inner::Sim(_) => return Err(sim::unsupported()),
+        };
+        let socket = unsafe { Socket::from_raw_socket(handle.handle as u64) };
         let output = f(&socket);
         mem::forget(socket);
         output
     }
 
     pub async fn connect<A: ToSocketAddrs>(addr: A) -> io::Result<TcpStream> {
+        #[cfg(feature = "sim")]
+        if let Some(network) = sim::current() {
+            return Self::connect_sim(&network, addr).await;
+        }
+
         let handle = Handle::current();
         let socket = super::socket::new()?;
 
@@ -113,7 +133,7 @@ impl TcpStream {
             }
         };
 
-        let event: Box<IoEvent> = IoEvent::new(&handle.callback_environ())?;
+        let event: Box<IoEvent> = IoEvent::new(&handle)?;
 
         let addrs = addr.to_socket_addrs().await?;
 
@@ -121,6 +141,11 @@ impl TcpStream {
         let mut tried = 0;
 
         for addr in addrs {
+            #[cfg(feature = "tracing")]
+            let target = addr;
+            #[cfg(feature = "tracing")]
+            tracing::trace!(addr = %target, "connecting");
+
             let sock_addr = SockAddr::from(addr);
             let addr = unsafe { sock_addr.as_ptr().read() };
             let len = sock_addr.len();
@@ -134,6 +159,17 @@ impl TcpStream {
             }
             .await;
 
+            #[cfg(feature = "tracing")]
+            match &result {
+                Ok(()) => tracing::debug!(addr = %target, "connected"),
+                Err(err) => tracing::debug!(
+                    addr = %target,
+                    os_error = err.raw_os_error(),
+                    error = %err,
+                    "connect attempt failed"
+                ),
+            }
+
             tried += 1;
             if result.is_ok() {
                 break;
@@ -149,9 +185,11 @@ impl TcpStream {
                     super::socket::cancel,
                     super::write::schedule,
                     super::socket::cancel,
-                    &Handle::current().callback_environ(),
+                    &handle,
                 )?;
-                Ok(TcpStream { inner })
+                Ok(TcpStream {
+                    inner: Inner::Os(inner),
+                })
             }
             Err(err) if tried > 0 => Err(err),
             _ => Err(io::Error::new(
@@ -161,12 +199,40 @@ impl TcpStream {
         }
     }
 
+    #[cfg(feature = "sim")]
+    async fn connect_sim<A: ToSocketAddrs>(
+        network: &sim::Network,
+        addr: A,
+    ) -> io::Result<TcpStream> {
+        let mut result = Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "the provided address couldn't be resolved",
+        ));
+        for addr in addr.to_socket_addrs().await? {
+            result = sim::Stream::connect(network, addr).await;
+            if result.is_ok() {
+                break;
+            }
+        }
+        result.map(|stream| TcpStream {
+            inner: Inner::Sim(stream),
+        })
+    }
+
     pub fn local_addr(&self) -> io::Result<SocketAddr> {
-        self.with_socket(|s| s.local_addr().map(|a| a.as_std().unwrap()))
+        match &self.inner {
+            Inner::Os(_) => self.with_socket(|s| s.local_addr().map(|a| a.as_std().unwrap())),
+            #[cfg(feature = "sim")]
+            Inner::Sim(stream) => Ok(stream.local_addr()),
+        }
     }
 
     pub fn peer_addr(&self) -> io::Result<SocketAddr> {
-        self.with_socket(|s| s.peer_addr().map(|a| a.as_std().unwrap()))
+        match &self.inner {
+            Inner::Os(_) => self.with_socket(|s| s.peer_addr().map(|a| a.as_std().unwrap())),
+            #[cfg(feature = "sim")]
+            Inner::Sim(stream) => Ok(stream.peer_addr()),
+        }
     }
 
     pub fn ttl(&self) -> io::Result<u32> {
@@ -194,7 +260,83 @@ impl TcpStream {
     }
 
     pub fn shutdown(&self, how: Shutdown) -> io::Result<()> {
-        self.with_socket(move |s| s.shutdown(how))
+        match &self.inner {
+            Inner::Os(_) => self.with_socket(move |s| s.shutdown(how)),
+            #[cfg(feature = "sim")]
+            Inner::Sim(stream) => stream.shutdown(how),
+        }
+    }
+}
+
+impl Inner {
+    pub(super) unsafe fn poll_read(
+        &self,
+        cx: &mut Context<'_>,
+        buf: *mut WSABUF,
+    ) -> Poll<io::Result<usize>> {
+        match self {
+            Inner::Os(handle) => handle.poll_read(cx, buf),
+            #[cfg(feature = "sim")]
+            Inner::Sim(stream) => stream.poll_read(cx, (*buf).buf as *mut u8, (*buf).len as usize),
+        }
+    }
+
+    pub(super) unsafe fn poll_write(
+        &self,
+        cx: &mut Context<'_>,
+        buf: *const WSABUF,
+    ) -> Poll<io::Result<usize>> {
+        match self {
+            Inner::Os(handle) => handle.poll_write(cx, buf),
+            #[cfg(feature = "sim")]
+            Inner::Sim(stream) => stream.poll_write(std::slice::from_raw_parts(
+                (*buf).buf as *const u8,
+                (*buf).len as usize,
+            )),
+        }
+    }
+
+    pub(super) unsafe fn cancel_read(&self, wait: bool) -> io::Result<()> {
+        match self {
+            Inner::Os(handle) => handle.cancel_read(wait),
+            #[cfg(feature = "sim")]
+            Inner::Sim(stream) => {
+                stream.cancel_read();
+                Ok(())
+            }
+        }
+    }
+
+    pub(super) unsafe fn cancel_write(&self, wait: bool) -> io::Result<()> {
+        match self {
+            Inner::Os(handle) => handle.cancel_write(wait),
+            #[cfg(feature = "sim")]
+            Inner::Sim(_) => Ok(()),
+        }
+    }
+
+    pub(super) fn read_stats(&self) -> &IoStats {
+        match self {
+            Inner::Os(handle) => handle.read_stats(),
+            #[cfg(feature = "sim")]
+            Inner::Sim(stream) => stream.read_stats(),
+        }
+    }
+
+    pub(super) fn write_stats(&self) -> &IoStats {
+        match self {
+            Inner::Os(handle) => handle.write_stats(),
+            #[cfg(feature = "sim")]
+            Inner::Sim(stream) => stream.write_stats(),
+        }
+    }
+
+    pub(super) fn created(&self) -> std::time::Instant {
+        match self {
+            Inner::Os(handle) => handle.created(),
+            #[cfg(feature = "sim")]
+            Inner::Sim(stream) => stream.created(),
+        }
     }
 }
 
@@ -9,12 +9,16 @@ use crate::task::waker::InlineWaker;
 use crate::threadpool::Handle;
 
 impl Handle {
+    #[track_caller]
     pub fn block_on<F, T>(&self, future: F) -> T
     where
         F: Future<Output = T> + Send + 'static,
         T: Send + 'static,
     {
         let future = self.spawn(future);
+        if let Some(driver) = self.driver() {
+            return driver.block_on(self, future);
+        }
         pin_mut!(future);
 
         let inline_waker = InlineWaker::default();
@@ -30,6 +34,7 @@ impl Handle {
     }
 }
 
+#[track_caller]
 pub fn block_on<F, T>(future: F) -> T
 where
     F: Future<Output = T> + Send + 'static,
@@ -1,8 +1,13 @@
 mod block_on;
+mod registry;
 mod spawn;
 mod util;
 mod waker;
 
 pub use block_on::*;
+pub(crate) use registry::{PollStart, TaskInfo, TaskRegistry};
+pub use registry::{TaskDump, TaskState};
+pub(crate) use spawn::TaskMeta;
 pub use spawn::*;
 pub use util::*;
+pub(crate) use waker::InlineWaker;
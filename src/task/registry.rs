@@ -0,0 +1,278 @@
+use std::{
+    cell::Cell,
+    collections::HashMap,
+    panic::Location,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::threadpool::{Handle, Priority};
+
+thread_local! {
+    /// The id of the task being polled on this thread, 0 outside of tasks, and whether it is
+    /// internal.
+    static CURRENT: Cell<(u64, bool)> = const { Cell::new((0, false)) };
+}
+
+#[derive(Debug, Clone)]
+pub struct TaskDump {
+    pub id: u64,
+    pub name: Option<String>,
+    pub location: &'static Location<'static>,
+    pub priority: Priority,
+    pub state: TaskState,
+    pub since_last_poll: Option<Duration>,
+    pub polls: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TaskState {
+    Scheduled,
+    Running,
+    Idle,
+}
+
+/// The live tasks of a pool, spread over shards so spawning and completing tasks on different
+/// threads rarely contend on the same lock.
+pub(crate) struct TaskRegistry {
+    epoch: Instant,
+    next_id: AtomicU64,
+    shards: Box<[Shard]>,
+}
+
+/// A poll of a task, from [`TaskInfo::set_running`] to [`TaskInfo::set_polled`].
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct PollStart {
+    #[cfg(feature = "console")]
+    pub(crate) at: u64,
+    state: u64,
+}
+
+#[repr(align(64))]
+#[derive(Default)]
+struct Shard(Mutex<HashMap<u64, Arc<TaskInfo>>>);
+
+pub(crate) struct TaskInfo {
+    pub(crate) id: u64,
+    pub(crate) name: Option<String>,
+    pub(crate) location: &'static Location<'static>,
+    pub(crate) priority: Priority,
+    /// Set for the pool's own tasks, which are left out of the registry and the task count.
+    pub(crate) internal: bool,
+    state: AtomicU64,
+    polls: AtomicU64,
+    last_poll: AtomicU64,
+    idle_since: AtomicU64,
+    scheduled_at: AtomicU64,
+}
+
+impl TaskRegistry {
+    const SHARDS: usize = 64;
+
+    pub(crate) fn new() -> Self {
+        Self {
+            epoch: Instant::now(),
+            next_id: AtomicU64::new(1),
+            shards: (0..Self::SHARDS).map(|_| Shard::default()).collect(),
+        }
+    }
+
+    /// Consecutive ids land in different shards.
+    fn shard(&self, id: u64) -> &Mutex<HashMap<u64, Arc<TaskInfo>>> {
+        &self.shards[id as usize % Self::SHARDS].0
+    }
+
+    pub(crate) fn insert(
+        &self,
+        name: Option<String>,
+        location: &'static Location<'static>,
+        priority: Priority,
+    ) -> Arc<TaskInfo> {
+        let info = self.info(name, location, priority, false);
+        self.shard(info.id)
+            .lock()
+            .unwrap()
+            .insert(info.id, info.clone());
+        info
+    }
+
+    /// Creates the info of an internal task, which isn't registered.
+    #[cfg(feature = "console")]
+    pub(crate) fn internal(
+        &self,
+        name: Option<String>,
+        location: &'static Location<'static>,
+        priority: Priority,
+    ) -> Arc<TaskInfo> {
+        self.info(name, location, priority, true)
+    }
+
+    fn info(
+        &self,
+        name: Option<String>,
+        location: &'static Location<'static>,
+        priority: Priority,
+        internal: bool,
+    ) -> Arc<TaskInfo> {
+        Arc::new(TaskInfo {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            name,
+            location,
+            priority,
+            internal,
+            state: AtomicU64::new(TaskInfo::IDLE),
+            polls: AtomicU64::new(0),
+            last_poll: AtomicU64::new(0),
+            idle_since: AtomicU64::new(0),
+            scheduled_at: AtomicU64::new(0),
+        })
+    }
+
+    pub(crate) fn remove(&self, id: u64) {
+        self.shard(id).lock().unwrap().remove(&id);
+    }
+
+    /// Nanoseconds since the registry was created, never zero so it can be told apart from "never".
+    pub(crate) fn now(&self) -> u64 {
+        (self.epoch.elapsed().as_nanos() as u64).max(1)
+    }
+
+    pub(crate) fn snapshot(&self) -> Vec<Arc<TaskInfo>> {
+        let mut tasks = Vec::new();
+        for shard in self.shards.iter() {
+            tasks.extend(shard.0.lock().unwrap().values().cloned());
+        }
+        tasks
+    }
+}
+
+impl TaskInfo {
+    const IDLE: u64 = 0;
+    const SCHEDULED: u64 = 1;
+    const RUNNING: u64 = 2;
+    /// The state takes the low bits, the rest counts polls so a poll that ended can't mark the
+    /// task idle while a later poll already started on another thread.
+    const STATE_MASK: u64 = 0b11;
+    const POLL: u64 = 0b100;
+
+    pub(crate) fn set_scheduled(&self, now: u64) {
+        self.scheduled_at.store(now, Ordering::Relaxed);
+        self.state
+            .fetch_update(Ordering::AcqRel, Ordering::Relaxed, |state| {
+                Some(state & !Self::STATE_MASK | Self::SCHEDULED)
+            })
+            .ok();
+    }
+
+    /// Returns the poll that started, along with how long the task was queued for if it was
+    /// scheduled since its last poll.
+    pub(crate) fn set_running(&self, now: u64) -> (PollStart, Option<u64>) {
+        CURRENT.with(|c| c.set((self.id, self.internal)));
+        self.last_poll.store(now, Ordering::Relaxed);
+        let previous = self
+            .state
+            .fetch_update(Ordering::AcqRel, Ordering::Relaxed, |state| {
+                Some(Self::next_poll(state))
+            })
+            .unwrap();
+        let start = PollStart {
+            #[cfg(feature = "console")]
+            at: now,
+            state: Self::next_poll(previous),
+        };
+        let wait = match self.scheduled_at.swap(0, Ordering::Relaxed) {
+            0 => None,
+            scheduled_at => Some(now.saturating_sub(scheduled_at)),
+        };
+        (start, wait)
+    }
+
+    fn next_poll(state: u64) -> u64 {
+        ((state & !Self::STATE_MASK) + Self::POLL) | Self::RUNNING
+    }
+
+    pub(crate) fn set_polled(&self, now: u64, start: PollStart) {
+        CURRENT.with(|c| c.set((0, false)));
+        self.idle_since.store(now, Ordering::Relaxed);
+        // If the task was woken up while running it stays scheduled.
+        self.state
+            .compare_exchange(
+                start.state,
+                start.state & !Self::STATE_MASK | Self::IDLE,
+                Ordering::AcqRel,
+                Ordering::Relaxed,
+            )
+            .ok();
+        self.polls.fetch_add(1, Ordering::Release);
+    }
+
+    /// The id of the task being polled on the current thread, if any.
+    pub(crate) fn current() -> Option<u64> {
+        Some(CURRENT.with(|c| c.get().0)).filter(|&id| id != 0)
+    }
+
+    /// Whether the task being polled on the current thread is one of the pool's own, whose IO is
+    /// left out of the pool's accounting.
+    #[cfg(all(feature = "io", feature = "io-shared"))]
+    pub(crate) fn current_is_internal() -> bool {
+        CURRENT.with(|c| c.get().1)
+    }
+
+    pub(crate) fn state(&self) -> TaskState {
+        match self.state.load(Ordering::Acquire) & Self::STATE_MASK {
+            Self::SCHEDULED => TaskState::Scheduled,
+            Self::RUNNING => TaskState::Running,
+            _ => TaskState::Idle,
+        }
+    }
+
+    pub(crate) fn last_poll(&self) -> Option<u64> {
+        match self.last_poll.load(Ordering::Relaxed) {
+            0 => None,
+            last_poll => Some(last_poll),
+        }
+    }
+
+    pub(crate) fn idle_since(&self) -> Option<u64> {
+        match self.idle_since.load(Ordering::Relaxed) {
+            0 => None,
+            idle_since => Some(idle_since),
+        }
+    }
+
+    pub(crate) fn polls(&self) -> u64 {
+        self.polls.load(Ordering::Acquire)
+    }
+
+    pub(crate) fn dump(&self, now: u64) -> TaskDump {
+        TaskDump {
+            id: self.id,
+            name: self.name.clone(),
+            location: self.location,
+            priority: self.priority,
+            state: self.state(),
+            since_last_poll: self
+                .last_poll()
+                .map(|last_poll| Duration::from_nanos(now.saturating_sub(last_poll))),
+            polls: self.polls(),
+        }
+    }
+}
+
+impl Handle {
+    pub fn dump_tasks(&self) -> Vec<TaskDump> {
+        let registry = self.registry();
+        let now = registry.now();
+
+        let mut dump: Vec<_> = registry
+            .snapshot()
+            .into_iter()
+            .map(|info| info.dump(now))
+            .collect();
+        dump.sort_by_key(|task| task.id);
+        dump
+    }
+}
@@ -3,20 +3,57 @@ use std::{
     fmt,
     future::Future,
     mem::{self, ManuallyDrop},
+    panic::Location,
     pin::Pin,
+    ptr,
+    sync::Arc,
     task::{Context, Poll},
 };
 
-use async_task::{Runnable, Task};
-use concurrent_queue::ConcurrentQueue;
+use async_task::{Runnable, ScheduleInfo, Task, WithInfo};
+use pin_project_lite::pin_project;
 use pin_utils::pin_mut;
 
 use winapi::um::winnt::{PTP_CALLBACK_INSTANCE, PTP_WORK};
 
-use crate::threadpool::Handle;
+use super::TaskInfo;
+use crate::threadpool::{Handle, TaskGuard};
 
 pub struct JoinHandle<T> {
-    task: ManuallyDrop<Task<T>>,
+    task: ManuallyDrop<Task<T, TaskMeta>>,
+}
+
+/// Returned by [`Handle::try_spawn`] when the queue is full, holding the future that wasn't
+/// spawned.
+pub struct TrySpawnError<F> {
+    future: F,
+}
+
+/// Stored once in the task's header, where running and scheduling the task borrow it from.
+pub(crate) struct TaskMeta {
+    pub(crate) handle: Handle,
+    pub(crate) info: Arc<TaskInfo>,
+}
+
+pin_project! {
+    struct Tracked<F> {
+        #[pin]
+        future: F,
+        guard: TaskGuard,
+    }
+}
+
+impl<F: Future> Future for Tracked<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let poll = this.future.poll(cx);
+        if poll.is_ready() {
+            this.guard.complete();
+        }
+        poll
+    }
 }
 
 impl<T> Future for JoinHandle<T> {
@@ -30,6 +67,16 @@ impl<T> Future for JoinHandle<T> {
 }
 
 impl<T> JoinHandle<T> {
+    /// Returns the id of the task, as found in task dumps.
+    pub fn id(&self) -> u64 {
+        self.task.metadata().info.id
+    }
+
+    #[cfg(feature = "console")]
+    pub(crate) fn is_finished(&self) -> bool {
+        self.task.is_finished()
+    }
+
     pub async fn cancel(self) -> Option<T> {
         let mut this = self;
         let output = unsafe { ManuallyDrop::take(&mut this.task) }.cancel().await;
@@ -48,7 +95,8 @@ impl<T> Drop for JoinHandle<T> {
 impl<T> fmt::Debug for JoinHandle<T> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("JoinHandle")
-            .field("task", &*self.task)
+            .field("id", &self.id())
+            .field("finished", &self.task.is_finished())
             .finish()
     }
 }
@@ -56,22 +104,134 @@ impl<T> fmt::Debug for JoinHandle<T> {
 pub(crate) unsafe extern "system" fn callback(
     instance: PTP_CALLBACK_INSTANCE,
     context: *mut c_void,
-    _work: PTP_WORK,
+    work: PTP_WORK,
 ) {
-    let context = context as *const ConcurrentQueue<(Runnable, Handle)>;
-    let queue = &*context;
-    let (runnable, mut handle) = queue.pop().unwrap();
+    crate::threadpool::run_tasks(context, work, |runnable| {
+        super::util::set_callback_instance(instance);
+        run_task(runnable, |handle| {
+            if super::util::set_callback_instance(ptr::null_mut()) {
+                handle.blocking_ended();
+            }
+        });
+    })
+}
 
-    handle.callback_instance.replace(instance);
-    let _context = handle.enter();
+/// Polls a task on the current thread within the context of its pool, then passes the pool to
+/// `after`.
+pub(crate) fn run_task(runnable: Runnable<TaskMeta>, after: impl FnOnce(&Handle)) {
+    // Dropped after the poll so a task left pending without a waker anywhere gets closed and
+    // its future dropped, where async-task would free it without dropping the future otherwise.
+    // Until then it also keeps the task's metadata alive, and the pool along with it.
+    let _task = runnable.waker();
+    let meta = unsafe { &*(runnable.metadata() as *const TaskMeta) };
+    let handle = &meta.handle;
+
+    let _context = handle.enter_borrowed();
     #[cfg(feature = "tracing")]
     let _span = handle.enter_span();
 
+    let hooks = handle.hooks();
+    hooks.thread_started();
+    hooks.task_poll_start();
+    let started = handle.poll_started(&meta.info);
+    #[cfg(feature = "metrics")]
+    let start = std::time::Instant::now();
     std::panic::catch_unwind(move || runnable.run()).ok();
+    #[cfg(feature = "metrics")]
+    handle.task_metrics().task_polled(start.elapsed());
+    handle.poll_ended(&meta.info, started);
+    hooks.task_poll_end();
+    after(handle);
 }
 
 impl Handle {
+    /// Spawns `future` on the pool.
+    ///
+    /// Once the pool is shutting down the task is cancelled right away, so awaiting the returned
+    /// handle panics and [`JoinHandle::cancel`] returns `None`.
+    #[track_caller]
     pub fn spawn<F, T>(&self, future: F) -> JoinHandle<T>
+    where
+        F: Future<Output = T> + Send + 'static,
+        T: Send + 'static,
+    {
+        self.spawn_inner(None, future, Location::caller())
+    }
+
+    /// Spawns one of the pool's own tasks, see [`Handle::internal_task_guard`].
+    #[cfg(feature = "console")]
+    #[track_caller]
+    pub(crate) fn spawn_internal<F>(&self, name: &str, future: F) -> JoinHandle<()>
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let guard = self.internal_task_guard(Some(name.to_owned()), Location::caller());
+        self.spawn_guarded(guard, future)
+    }
+
+    #[track_caller]
+    pub fn spawn_named<F, T>(&self, name: impl Into<String>, future: F) -> JoinHandle<T>
+    where
+        F: Future<Output = T> + Send + 'static,
+        T: Send + 'static,
+    {
+        self.spawn_inner(Some(name.into()), future, Location::caller())
+    }
+
+    /// Spawns `future` unless the queue of the handle's priority is over the limit set with
+    /// [`Builder::max_queued_tasks`](crate::threadpool::Builder::max_queued_tasks).
+    #[track_caller]
+    pub fn try_spawn<F, T>(&self, future: F) -> Result<JoinHandle<T>, TrySpawnError<F>>
+    where
+        F: Future<Output = T> + Send + 'static,
+        T: Send + 'static,
+    {
+        if !self.reserve() {
+            return Err(TrySpawnError { future });
+        }
+        let task = self.spawn_inner(None, future, Location::caller());
+        self.release();
+        Ok(task)
+    }
+
+    /// Spawns `future` once the queue of the handle's priority is below the limit set with
+    /// [`Builder::max_queued_tasks`](crate::threadpool::Builder::max_queued_tasks).
+    #[track_caller]
+    #[allow(clippy::async_yields_async)]
+    pub fn spawn_with_backpressure<F, T>(
+        &self,
+        future: F,
+    ) -> impl Future<Output = JoinHandle<T>> + Send + 'static
+    where
+        F: Future<Output = T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let handle = self.clone();
+        let location = Location::caller();
+        async move {
+            let reserved = handle.room().await;
+            let task = handle.spawn_inner(None, future, location);
+            if reserved {
+                handle.release();
+            }
+            task
+        }
+    }
+
+    fn spawn_inner<F, T>(
+        &self,
+        name: Option<String>,
+        future: F,
+        location: &'static Location<'static>,
+    ) -> JoinHandle<T>
+    where
+        F: Future<Output = T> + Send + 'static,
+        T: Send + 'static,
+    {
+        self.spawn_guarded(self.task_guard(name, location), future)
+    }
+
+    fn spawn_guarded<F, T>(&self, guard: TaskGuard, future: F) -> JoinHandle<T>
     where
         F: Future<Output = T> + Send + 'static,
         T: Send + 'static,
@@ -80,22 +240,44 @@ impl Handle {
         let handle = self.clone();
         #[cfg(feature = "tracing")]
         let mut handle = self.clone();
+
+        let info = guard.info().clone();
+        let future = Tracked { future, guard };
+
         #[cfg(feature = "tracing")]
         {
-            handle.span = match &handle.span {
-                Some(parent) => Some(tracing::trace_span!(
+            handle.span = Some(match &handle.span {
+                Some(parent) => tracing::trace_span!(
                     parent: parent,
                     "task",
-                    handle = ?handle
-                )),
-                None => Some(tracing::trace_span!("task", handle = ?handle)),
-            }
+                    id = info.id,
+                    name = ?info.name,
+                    priority = ?info.priority,
+                ),
+                None => tracing::trace_span!(
+                    "task",
+                    id = info.id,
+                    name = ?info.name,
+                    priority = ?info.priority,
+                ),
+            });
+            tracing::trace!(
+                parent: handle.span_id(),
+                location = %info.location,
+                "task spawned"
+            );
         }
 
-        let schedule = move |runnable| handle.push_task(runnable);
-
-        let (runnable, task) = async_task::spawn(future, schedule);
-        runnable.schedule();
+        let closed = handle.is_closed();
+        let (runnable, task) = async_task::Builder::new()
+            .metadata(TaskMeta { handle, info })
+            .spawn(move |_| future, WithInfo(schedule));
+        if closed {
+            // Cancels the task, which drops the future.
+            drop(runnable);
+        } else {
+            runnable.schedule();
+        }
 
         JoinHandle {
             task: ManuallyDrop::new(task),
@@ -103,6 +285,48 @@ impl Handle {
     }
 }
 
+fn schedule(runnable: Runnable<TaskMeta>, schedule_info: ScheduleInfo) {
+    let meta = runnable.metadata();
+    #[cfg(feature = "tracing")]
+    tracing::trace!(parent: meta.handle.span_id(), "task woken");
+    meta.info.set_scheduled(meta.handle.registry().now());
+    #[cfg(feature = "console")]
+    if let Some(console) = meta.handle.console().filter(|_| !meta.info.internal) {
+        console.emit(crate::console::Event::Wake { id: meta.info.id });
+    }
+    crate::threadpool::schedule(runnable, schedule_info.woken_while_running)
+}
+
+impl<F> TrySpawnError<F> {
+    pub fn into_inner(self) -> F {
+        self.future
+    }
+}
+
+impl<F> fmt::Debug for TrySpawnError<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TrySpawnError").finish()
+    }
+}
+
+impl<F> fmt::Display for TrySpawnError<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("the task queue is full")
+    }
+}
+
+impl<F> std::error::Error for TrySpawnError<F> {}
+
+impl fmt::Debug for TaskMeta {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TaskMeta")
+            .field("id", &self.info.id)
+            .field("priority", &self.handle.priority())
+            .finish()
+    }
+}
+
+#[track_caller]
 pub fn spawn<F, T>(future: F) -> JoinHandle<T>
 where
     F: Future<Output = T> + Send + 'static,
@@ -110,3 +334,12 @@ where
 {
     Handle::current().spawn(future)
 }
+
+#[track_caller]
+pub fn spawn_named<F, T>(name: impl Into<String>, future: F) -> JoinHandle<T>
+where
+    F: Future<Output = T> + Send + 'static,
+    T: Send + 'static,
+{
+    Handle::current().spawn_named(name, future)
+}
@@ -1,19 +1,40 @@
 use std::{
+    cell::Cell,
     future::Future,
     pin::Pin,
+    ptr,
     task::{Context, Poll},
 };
 
-use winapi::{shared::minwindef::TRUE, um::threadpoolapiset::CallbackMayRunLong};
+use winapi::{
+    shared::minwindef::TRUE,
+    um::{threadpoolapiset::CallbackMayRunLong, winnt::PTP_CALLBACK_INSTANCE},
+};
 
 use crate::threadpool::Handle;
 
+thread_local! {
+    static CALLBACK_INSTANCE: Cell<PTP_CALLBACK_INSTANCE> = const { Cell::new(ptr::null_mut()) };
+    static BLOCKING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Sets the callback instance of the task running on the current thread, null outside of tasks.
+/// Returns whether the previous task declared it was blocking.
+pub(crate) fn set_callback_instance(instance: PTP_CALLBACK_INSTANCE) -> bool {
+    CALLBACK_INSTANCE.with(|i| i.set(instance));
+    BLOCKING.with(|b| b.replace(false))
+}
+
 impl Handle {
     pub fn may_block(&self) -> bool {
-        match self.callback_instance {
-            Some(instance) => unsafe { CallbackMayRunLong(instance) == TRUE },
-            None => false,
+        let instance = CALLBACK_INSTANCE.with(|i| i.get());
+        if instance.is_null() {
+            return false;
+        }
+        if !BLOCKING.with(|b| b.replace(true)) {
+            self.blocking_started();
         }
+        unsafe { CallbackMayRunLong(instance) == TRUE }
     }
 }
 
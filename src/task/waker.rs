@@ -31,18 +31,24 @@ impl InlineWaker {
         unsafe { Waker::from_raw(clone_fn(state as *const ())) }
     }
 
-    pub fn wake(&self) {
-        if self
-            .state
-            .compare_exchange(0, 1, Ordering::AcqRel, Ordering::Acquire)
-            .is_ok()
-        {
+    /// Returns whether the waker wasn't woken up already.
+    pub fn wake(&self) -> bool {
+        if self.state.swap(1, Ordering::AcqRel) == 0 {
             unsafe {
                 WakeByAddressAll(self.get_mut_ptr() as *mut c_void);
             }
+            true
+        } else {
+            false
         }
     }
 
+    /// Consumes a wakeup so the waker can be waited on again.
+    pub fn reset(&self) {
+        // Swapping acquires whatever was done before every wakeup so far.
+        self.state.swap(0, Ordering::AcqRel);
+    }
+
     pub fn wait(&self) {
         unsafe {
             while self.state.load(Ordering::Acquire) != 1 {
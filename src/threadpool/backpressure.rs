@@ -0,0 +1,170 @@
+use std::{
+    collections::VecDeque,
+    future::Future,
+    pin::Pin,
+    sync::atomic::Ordering,
+    task::{Context, Poll, Waker},
+};
+
+use super::{Handle, TaskQueue};
+
+/// Waits until there is room in the queue of the handle's priority and reserves it, resolving to
+/// whether it did. Nothing is reserved once the pool is shutting down.
+pub(crate) struct Room<'a> {
+    handle: &'a Handle,
+    /// The id of the waker registered in the queue's waiters.
+    slot: Option<u64>,
+}
+
+/// Tasks waiting for room, each registered once under its own id.
+#[derive(Default)]
+pub(crate) struct Waiters {
+    next: u64,
+    list: VecDeque<(u64, Waker)>,
+}
+
+impl TaskQueue {
+    /// Reserves room for a task about to be spawned, to be released with [`TaskQueue::release`]
+    /// once it is queued.
+    fn reserve(&self) -> bool {
+        self.queued
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |queued| {
+                (queued < self.limit).then_some(queued + 1)
+            })
+            .is_ok()
+    }
+
+    fn release(&self) {
+        self.queued.fetch_sub(1, Ordering::SeqCst);
+        self.made_room();
+    }
+
+    /// Wakes up a task waiting for room after a task was taken out of the queue.
+    pub(crate) fn made_room(&self) {
+        if self.waiting.load(Ordering::SeqCst) == 0
+            || self.queued.load(Ordering::SeqCst) >= self.limit
+        {
+            return;
+        }
+
+        let waker = self.waiters.lock().unwrap().list.pop_front();
+        if let Some((_, waker)) = waker {
+            self.waiting.fetch_sub(1, Ordering::SeqCst);
+            waker.wake();
+        }
+    }
+
+    /// Wakes up every task waiting for room, used when the pool shuts down.
+    pub(crate) fn wake_waiters(&self) {
+        let waiters = std::mem::take(&mut self.waiters.lock().unwrap().list);
+        self.waiting.fetch_sub(waiters.len(), Ordering::SeqCst);
+        for (_, waker) in waiters {
+            waker.wake();
+        }
+    }
+}
+
+impl Handle {
+    /// Reserves room in the queue of the handle's priority unless it is at its limit.
+    pub(crate) fn reserve(&self) -> bool {
+        self.inner.queue(self.priority).reserve()
+    }
+
+    /// Releases room reserved with [`Handle::reserve`] or [`Handle::room`].
+    pub(crate) fn release(&self) {
+        self.inner.queue(self.priority).release()
+    }
+
+    pub(crate) fn room(&self) -> Room<'_> {
+        Room {
+            handle: self,
+            slot: None,
+        }
+    }
+}
+
+impl<'a> Room<'a> {
+    fn queue(&self) -> &'a TaskQueue {
+        self.handle.inner.queue(self.handle.priority)
+    }
+
+    fn try_reserve(&mut self) -> Option<bool> {
+        let reserved = if self.queue().reserve() {
+            true
+        } else if self.handle.is_closed() {
+            false
+        } else {
+            return None;
+        };
+        self.unregister();
+        Some(reserved)
+    }
+
+    fn register(&mut self, waker: &Waker) {
+        let queue = self.queue();
+        let mut waiters = queue.waiters.lock().unwrap();
+        let registered = self
+            .slot
+            .and_then(|id| waiters.list.iter_mut().find(|(slot, _)| *slot == id));
+        match registered {
+            Some((_, registered)) => {
+                if !registered.will_wake(waker) {
+                    *registered = waker.clone();
+                }
+            }
+            // Not registered yet, or woken up by room that was taken before it got polled.
+            None => {
+                let id = waiters.next;
+                waiters.next += 1;
+                waiters.list.push_back((id, waker.clone()));
+                queue.waiting.fetch_add(1, Ordering::SeqCst);
+                self.slot = Some(id);
+            }
+        }
+    }
+
+    /// Removes the registered waker, returning whether it was woken up instead.
+    fn unregister(&mut self) -> bool {
+        let id = match self.slot.take() {
+            Some(id) => id,
+            None => return false,
+        };
+        let queue = self.queue();
+        let mut waiters = queue.waiters.lock().unwrap();
+        match waiters.list.iter().position(|(slot, _)| *slot == id) {
+            Some(position) => {
+                waiters.list.remove(position);
+                queue.waiting.fetch_sub(1, Ordering::SeqCst);
+                false
+            }
+            None => true,
+        }
+    }
+}
+
+impl Future for Room<'_> {
+    type Output = bool;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        if let Some(reserved) = this.try_reserve() {
+            return Poll::Ready(reserved);
+        }
+
+        this.register(cx.waker());
+        // Room might have been made before the waker was registered.
+        match this.try_reserve() {
+            Some(reserved) => Poll::Ready(reserved),
+            None => Poll::Pending,
+        }
+    }
+}
+
+impl Drop for Room<'_> {
+    fn drop(&mut self) {
+        // Pass the room this was woken up for on to the next waiter.
+        if self.unregister() {
+            self.queue().made_room();
+        }
+    }
+}
@@ -0,0 +1,126 @@
+use std::{env, ffi::OsString, io, str::FromStr};
+
+use super::{Builder, Priority};
+
+impl Builder {
+    /// Creates a builder configured from the environment, see [`Builder::with_env`].
+    pub fn from_env() -> io::Result<Builder> {
+        Builder::new().with_env()
+    }
+
+    /// Overrides the configuration with the environment variables that are set.
+    ///
+    /// - `WAE_MIN_THREADS` and `WAE_MAX_THREADS`: thread counts
+    /// - `WAE_ADAPTIVE_THREADS`: `MIN..MAX` bounds for [`Builder::adaptive_threads`]
+    /// - `WAE_CURRENT_THREAD`: whether to use [`Builder::current_thread`]
+    /// - `WAE_SEED`: the seed for [`Builder::deterministic`]
+    /// - `WAE_PRIORITY`: `high`, `normal` or `low`, see [`Builder::priority`]
+    /// - `WAE_MAX_QUEUED_TASKS`: see [`Builder::max_queued_tasks`]
+    /// - `WAE_STACK_RESERVE` and `WAE_STACK_COMMIT`: thread stack sizes in bytes
+    /// - `WAE_NET`: whether to initialize Winsock, ignored without the `net` feature
+    ///
+    /// Booleans are `1`, `true`, `yes` or `on` and `0`, `false`, `no` or `off`. Invalid values
+    /// fail with an error naming the variable.
+    pub fn with_env(self) -> io::Result<Builder> {
+        self.with_env_from(|name| env::var_os(name))
+    }
+
+    /// Like [`Builder::with_env`], looking variables up with `lookup` instead of in the process
+    /// environment.
+    pub fn with_env_from(
+        mut self,
+        lookup: impl Fn(&str) -> Option<OsString>,
+    ) -> io::Result<Builder> {
+        if let Some(min) = var(&lookup, "WAE_MIN_THREADS", number, "a number of threads")? {
+            self.min_threads = min;
+        }
+        if let Some(max) = var(&lookup, "WAE_MAX_THREADS", number, "a number of threads")? {
+            self.max_threads = max;
+        }
+        if let Some((min, max)) = var(
+            &lookup,
+            "WAE_ADAPTIVE_THREADS",
+            bounds,
+            "`MIN..MAX` thread counts",
+        )? {
+            self = self.adaptive_threads(min, max);
+        }
+        if let Some(current_thread) = var(&lookup, "WAE_CURRENT_THREAD", boolean, "a boolean")? {
+            self.current_thread = current_thread;
+        }
+        if let Some(seed) = var(&lookup, "WAE_SEED", number, "a 64-bit seed")? {
+            self = self.deterministic(seed);
+        }
+        if let Some(priority) = var(
+            &lookup,
+            "WAE_PRIORITY",
+            priority,
+            "`high`, `normal` or `low`",
+        )? {
+            self.priority = priority;
+        }
+        if let Some(limit) = var(&lookup, "WAE_MAX_QUEUED_TASKS", number, "a number of tasks")? {
+            self.max_queued_tasks = [limit; 3];
+        }
+        if let Some(reserve) = var(&lookup, "WAE_STACK_RESERVE", number, "a size in bytes")? {
+            self.stack_reserve = Some(reserve);
+        }
+        if let Some(commit) = var(&lookup, "WAE_STACK_COMMIT", number, "a size in bytes")? {
+            self.stack_commit = Some(commit);
+        }
+        // Still validated without the `net` feature so typos don't go unnoticed.
+        let net = var(&lookup, "WAE_NET", boolean, "a boolean")?;
+        #[cfg(feature = "net")]
+        if let Some(net) = net {
+            self.net = net;
+        }
+        #[cfg(not(feature = "net"))]
+        let _ = net;
+        Ok(self)
+    }
+}
+
+fn var<T>(
+    lookup: impl Fn(&str) -> Option<OsString>,
+    name: &str,
+    parse: fn(&str) -> Option<T>,
+    expected: &str,
+) -> io::Result<Option<T>> {
+    let value = match lookup(name) {
+        Some(value) => value,
+        None => return Ok(None),
+    };
+    match value.to_str().and_then(|value| parse(value.trim())) {
+        Some(value) => Ok(Some(value)),
+        None => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("invalid {} {:?}: expected {}", name, value, expected),
+        )),
+    }
+}
+
+fn number<T: FromStr>(value: &str) -> Option<T> {
+    value.parse().ok()
+}
+
+fn bounds(value: &str) -> Option<(u32, u32)> {
+    let (min, max) = value.split_once("..")?;
+    Some((number(min)?, number(max)?))
+}
+
+fn boolean(value: &str) -> Option<bool> {
+    match value.to_ascii_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Some(true),
+        "0" | "false" | "no" | "off" => Some(false),
+        _ => None,
+    }
+}
+
+fn priority(value: &str) -> Option<Priority> {
+    match value.to_ascii_lowercase().as_str() {
+        "high" => Some(Priority::High),
+        "normal" => Some(Priority::Normal),
+        "low" => Some(Priority::Low),
+        _ => None,
+    }
+}
@@ -0,0 +1,89 @@
+use std::{
+    cell::RefCell,
+    fmt,
+    panic::{self, AssertUnwindSafe},
+    sync::Arc,
+};
+
+pub(crate) type Hook = Arc<dyn Fn() + Send + Sync>;
+
+#[derive(Clone, Default)]
+pub(crate) struct Hooks {
+    pub(crate) on_thread_start: Option<Hook>,
+    pub(crate) on_thread_stop: Option<Hook>,
+    pub(crate) on_task_poll_start: Option<Hook>,
+    pub(crate) on_task_poll_end: Option<Hook>,
+    pub(crate) on_wakeup: Option<Hook>,
+}
+
+struct ThreadGuard {
+    on_thread_stop: Option<Hook>,
+}
+
+thread_local! {
+    static THREAD: RefCell<Option<ThreadGuard>> = const { RefCell::new(None) };
+}
+
+impl Hooks {
+    pub(crate) fn thread_started(&self) {
+        if self.on_thread_start.is_none() && self.on_thread_stop.is_none() {
+            return;
+        }
+
+        THREAD.with(|t| {
+            let mut t = t.borrow_mut();
+            if t.is_none() {
+                if let Some(f) = &self.on_thread_start {
+                    call(f);
+                }
+                // Worker threads exit when the pool shrinks or once it's closed, which runs the
+                // guard's destructor on the thread itself.
+                *t = Some(ThreadGuard {
+                    on_thread_stop: self.on_thread_stop.clone(),
+                });
+            }
+        })
+    }
+
+    #[inline]
+    pub(crate) fn task_poll_start(&self) {
+        if let Some(f) = &self.on_task_poll_start {
+            call(f);
+        }
+    }
+
+    #[inline]
+    pub(crate) fn task_poll_end(&self) {
+        if let Some(f) = &self.on_task_poll_end {
+            call(f);
+        }
+    }
+}
+
+/// Runs a hook, which must not unwind into the thread pool's callbacks.
+pub(crate) fn call(f: &Hook) {
+    if panic::catch_unwind(AssertUnwindSafe(|| f())).is_err() {
+        #[cfg(feature = "tracing")]
+        tracing::error!("a thread pool hook panicked");
+    }
+}
+
+impl Drop for ThreadGuard {
+    fn drop(&mut self) {
+        if let Some(f) = &self.on_thread_stop {
+            call(f);
+        }
+    }
+}
+
+impl fmt::Debug for Hooks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Hooks")
+            .field("on_thread_start", &self.on_thread_start.is_some())
+            .field("on_thread_stop", &self.on_thread_stop.is_some())
+            .field("on_task_poll_start", &self.on_task_poll_start.is_some())
+            .field("on_task_poll_end", &self.on_task_poll_end.is_some())
+            .field("on_wakeup", &self.on_wakeup.is_some())
+            .finish()
+    }
+}
@@ -0,0 +1,186 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    task::{Context, Poll, Waker},
+};
+
+use pin_utils::pin_mut;
+
+use super::{Handle, HandleInner, Priority};
+use crate::task::{InlineWaker, TaskInfo, TaskState};
+
+/// Resolves once the pool is idle, see [`Handle::wait_idle`].
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct WaitIdle {
+    handle: Handle,
+    /// Whether the pool was idle when last polled.
+    idle: bool,
+    /// The id of the waker registered in the pool's idle waiters.
+    slot: Option<u64>,
+}
+
+/// Tasks waiting for the pool to get idle, woken up all at once when it does.
+#[derive(Default)]
+pub(crate) struct IdleWaiters {
+    /// The number of registered wakers, so the pool doesn't check for idleness when nobody waits.
+    waiting: AtomicUsize,
+    wakers: Mutex<Wakers>,
+}
+
+#[derive(Default)]
+struct Wakers {
+    next: u64,
+    map: HashMap<u64, Waker>,
+}
+
+impl Handle {
+    /// Waits until no task is queued or being polled and no IO operation is pending.
+    ///
+    /// The task waiting is left out, and so are sleeping tasks: timers don't count as activity.
+    /// The pool is only considered idle once it was on two polls in a row, so a task woken up by
+    /// an IO completion in between is always seen.
+    pub fn wait_idle(&self) -> WaitIdle {
+        WaitIdle {
+            handle: self.clone(),
+            idle: false,
+            slot: None,
+        }
+    }
+
+    /// Blocks the current thread until the pool is idle, see [`Handle::wait_idle`].
+    ///
+    /// A [`Builder::current_thread`](super::Builder::current_thread) pool is driven while
+    /// waiting, unless another thread is already driving it.
+    pub fn block_until_idle(&self) {
+        let mut idle = self.wait_idle();
+        if let Some(driver) = self.driver() {
+            idle = match driver.try_block_on(self, idle) {
+                Ok(()) => return,
+                Err(idle) => idle,
+            };
+        }
+        pin_mut!(idle);
+
+        let inline_waker = InlineWaker::default();
+        let waker = inline_waker.get_waker();
+        let mut cx = Context::from_waker(&waker);
+        while idle.as_mut().poll(&mut cx).is_pending() {
+            inline_waker.wait();
+            inline_waker.reset();
+        }
+    }
+}
+
+impl Future for WaitIdle {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let this = self.get_mut();
+        let inner = &this.handle.inner;
+        // Registered first so the pool getting idle right after the check below wakes this up.
+        inner.idle_waiters.register(&mut this.slot, cx.waker());
+        let idle = inner.is_idle(TaskInfo::current());
+        if idle && this.idle {
+            inner.idle_waiters.unregister(&mut this.slot);
+            return Poll::Ready(());
+        }
+        this.idle = idle;
+        if idle {
+            // Check once more that nothing was woken up in between.
+            cx.waker().wake_by_ref();
+        }
+        Poll::Pending
+    }
+}
+
+impl Drop for WaitIdle {
+    fn drop(&mut self) {
+        self.handle.inner.idle_waiters.unregister(&mut self.slot);
+    }
+}
+
+impl IdleWaiters {
+    fn register(&self, slot: &mut Option<u64>, waker: &Waker) {
+        let mut wakers = self.wakers.lock().unwrap();
+        match slot.and_then(|id| wakers.map.get_mut(&id)) {
+            Some(registered) => {
+                if !registered.will_wake(waker) {
+                    *registered = waker.clone();
+                }
+            }
+            // Not registered yet, or woken up since.
+            None => {
+                let id = wakers.next;
+                wakers.next += 1;
+                wakers.map.insert(id, waker.clone());
+                self.waiting.fetch_add(1, Ordering::SeqCst);
+                *slot = Some(id);
+            }
+        }
+    }
+
+    fn unregister(&self, slot: &mut Option<u64>) {
+        if let Some(id) = slot.take() {
+            if self.wakers.lock().unwrap().map.remove(&id).is_some() {
+                self.waiting.fetch_sub(1, Ordering::SeqCst);
+            }
+        }
+    }
+
+    fn wake_all(&self) {
+        let wakers = std::mem::take(&mut self.wakers.lock().unwrap().map);
+        self.waiting.fetch_sub(wakers.len(), Ordering::SeqCst);
+        for waker in wakers.into_values() {
+            waker.wake();
+        }
+    }
+}
+
+impl HandleInner {
+    /// Wakes up the tasks waiting for the pool to get idle if it is, called whenever a task or an
+    /// IO operation might have been the last one active.
+    pub(crate) fn notify_idle(&self) {
+        if self.idle_waiters.waiting.load(Ordering::SeqCst) > 0 && self.is_idle(None) {
+            self.idle_waiters.wake_all();
+        }
+    }
+
+    /// Whether every task but `current` is idle, with no IO pending.
+    fn is_idle(&self, current: Option<u64>) -> bool {
+        let queued = Priority::ALL
+            .iter()
+            .map(|&priority| self.queue(priority))
+            .any(|queue| queue.queued.load(Ordering::SeqCst) > 0);
+        if queued || self.io_in_flight() {
+            return false;
+        }
+        self.registry
+            .snapshot()
+            .iter()
+            .filter(|info| Some(info.id) != current)
+            .all(|info| info.state() == TaskState::Idle)
+    }
+
+    #[cfg(all(feature = "io", feature = "io-shared"))]
+    fn io_in_flight(&self) -> bool {
+        if self.io_events.load(Ordering::Acquire) > 0 {
+            return true;
+        }
+        let registry = self.io.lock().unwrap().clone();
+        registry
+            .iter()
+            .filter_map(|io| io.upgrade())
+            .any(|io| io.is_pending())
+    }
+
+    #[cfg(not(all(feature = "io", feature = "io-shared")))]
+    fn io_in_flight(&self) -> bool {
+        false
+    }
+}
@@ -0,0 +1,266 @@
+use std::{
+    future::Future,
+    mem,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    task::{Context, Poll, Wake, Waker},
+    thread,
+};
+
+use pin_utils::pin_mut;
+
+use super::{
+    hooks::{self, Hook},
+    worker::Task,
+    Handle, HandleInner, Priority, TaskQueue,
+};
+use crate::task::InlineWaker;
+
+/// Runs the tasks of a current thread pool on the thread calling `block_on` or
+/// [`Handle::drive`].
+///
+/// The pool keeps a single thread around to receive IO completions, which only wake tasks up.
+pub(crate) struct Driver {
+    notifier: Arc<Notifier>,
+    driving: AtomicBool,
+    seeded: Option<Seeded>,
+}
+
+/// Picks the next task among every ready one at random, see [`Builder::deterministic`].
+///
+/// [`Builder::deterministic`]: super::Builder::deterministic
+struct Seeded {
+    seed: u64,
+    state: Mutex<SeededState>,
+}
+
+struct SeededState {
+    rng: u64,
+    ready: Vec<Task>,
+}
+
+struct Driving<'a> {
+    driver: &'a Driver,
+    block_on: bool,
+}
+
+/// Wakes up the driving thread when a task gets queued or the future it blocks on is woken up.
+struct Notifier {
+    waker: InlineWaker,
+    on_wakeup: Option<Hook>,
+}
+
+impl Driver {
+    pub(crate) fn new(on_wakeup: Option<Hook>, seed: Option<u64>) -> Self {
+        Self {
+            notifier: Arc::new(Notifier {
+                waker: InlineWaker::new(),
+                on_wakeup,
+            }),
+            driving: AtomicBool::new(false),
+            seeded: seed.map(|seed| Seeded {
+                seed,
+                state: Mutex::new(SeededState {
+                    rng: seed,
+                    ready: Vec::new(),
+                }),
+            }),
+        }
+    }
+
+    pub(super) fn push(&self, queue: &TaskQueue, task: Task) {
+        queue.queued.fetch_add(1, Ordering::SeqCst);
+        queue.queue.push(task).unwrap();
+        self.notifier.wake_by_ref();
+    }
+
+    /// Runs queued tasks until `future` completes.
+    #[track_caller]
+    pub(crate) fn block_on<F: Future>(&self, handle: &Handle, future: F) -> F::Output {
+        match self.try_block_on(handle, future) {
+            Ok(output) => output,
+            Err(_) => {
+                panic!("a current thread pool can only be driven by one `block_on` at a time")
+            }
+        }
+    }
+
+    /// Runs queued tasks until `future` completes, or gives it back if another thread is driving
+    /// the pool.
+    pub(crate) fn try_block_on<F: Future>(
+        &self,
+        handle: &Handle,
+        future: F,
+    ) -> Result<F::Output, F> {
+        let mut driving = match self.enter() {
+            Some(driving) => driving,
+            None => return Err(future),
+        };
+        driving.block_on = true;
+        pin_mut!(future);
+
+        let waker = Waker::from(self.notifier.clone());
+        let mut cx = Context::from_waker(&waker);
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return Ok(output);
+            }
+            if run_ready(&handle.inner, TaskQueue::MAX_BATCH) == 0 {
+                // Every task is idle, skip ahead to the next timer if the clock is paused.
+                #[cfg(feature = "time")]
+                if handle.inner.clock.advance_to_next_timer() {
+                    continue;
+                }
+                self.notifier.waker.wait();
+                self.notifier.waker.reset();
+            }
+        }
+    }
+
+    /// Runs up to `max_tasks` tasks unless another thread is driving the pool.
+    pub(super) fn try_run(&self, inner: &HandleInner, max_tasks: usize) -> usize {
+        match self.enter() {
+            Some(_driving) => {
+                self.notifier.waker.reset();
+                let ran = run_ready(inner, max_tasks);
+                // Same as in `block_on`, which nothing else would do for the pool.
+                #[cfg(feature = "time")]
+                if ran == 0 && inner.clock.advance_to_next_timer() {
+                    return run_ready(inner, max_tasks);
+                }
+                ran
+            }
+            None => 0,
+        }
+    }
+
+    /// Drops the tasks set aside by a seeded driver, used when the pool shuts down.
+    pub(super) fn clear(&self) {
+        if let Some(seeded) = &self.seeded {
+            // Dropped outside of the lock since cancelled tasks can wake others up.
+            let ready = mem::take(&mut seeded.state.lock().unwrap().ready);
+            drop(ready);
+        }
+    }
+
+    fn next_task(&self, inner: &HandleInner) -> Option<Task> {
+        let seeded = match &self.seeded {
+            Some(seeded) => seeded,
+            None => return next_task(inner),
+        };
+
+        let mut state = seeded.state.lock().unwrap();
+        while let Some(task) = next_task(inner) {
+            state.ready.push(task);
+        }
+        if state.ready.is_empty() {
+            return None;
+        }
+        let index = (state.next() % state.ready.len() as u64) as usize;
+        Some(state.ready.swap_remove(index))
+    }
+
+    fn enter(&self) -> Option<Driving<'_>> {
+        self.driving
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .ok()
+            .map(|_| Driving {
+                driver: self,
+                block_on: false,
+            })
+    }
+}
+
+impl SeededState {
+    /// splitmix64, which is plenty for shuffling tasks around.
+    fn next(&mut self) -> u64 {
+        self.rng = self.rng.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.rng;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
+
+impl Drop for Driving<'_> {
+    fn drop(&mut self) {
+        let panicking = self.block_on && thread::panicking();
+        if let Some(seeded) = self.driver.seeded.as_ref().filter(|_| panicking) {
+            eprintln!(
+                "wae: deterministic pool panicked with seed {}, set WAE_SEED={} to replay",
+                seeded.seed, seeded.seed
+            );
+        }
+        self.driver.driving.store(false, Ordering::Release);
+    }
+}
+
+impl Wake for Notifier {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref()
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        if self.waker.wake() {
+            if let Some(f) = &self.on_wakeup {
+                hooks::call(f);
+            }
+        }
+    }
+}
+
+impl Handle {
+    /// Runs up to `max_tasks` ready tasks on the current thread and returns how many ran.
+    ///
+    /// This is how a host event loop drives a [`Builder::current_thread`](super::Builder) pool,
+    /// [`Builder::on_wakeup`](super::Builder::on_wakeup) tells it when there is work to do. Other
+    /// pools run the tasks taken off their queues inline as well. Returns 0 when another thread
+    /// is already driving the pool.
+    ///
+    /// When no task of a current thread pool is ready and its clock is paused, the clock skips
+    /// ahead to the next timer first.
+    pub fn drive(&self, max_tasks: usize) -> usize {
+        match self.driver() {
+            Some(driver) => driver.try_run(&self.inner, max_tasks),
+            None => run_ready(&self.inner, max_tasks),
+        }
+    }
+
+    /// Runs tasks on the current thread until none is ready anymore and returns how many ran.
+    ///
+    /// Tasks that keep waking themselves up keep this from returning, and so do sleeps on a
+    /// paused clock since it skips ahead to them.
+    pub fn run_until_stalled(&self) -> usize {
+        let mut ran = 0;
+        loop {
+            match self.drive(TaskQueue::MAX_BATCH) {
+                0 => return ran,
+                n => ran += n,
+            }
+        }
+    }
+}
+
+/// Runs up to `max_tasks` queued tasks on the current thread, highest priority first unless the
+/// pool is seeded.
+fn run_ready(inner: &HandleInner, max_tasks: usize) -> usize {
+    for ran in 0..max_tasks {
+        let task = match &inner.driver {
+            Some(driver) => driver.next_task(inner),
+            None => next_task(inner),
+        };
+        match task {
+            Some(task) => crate::task::run_task(task, |_| {}),
+            None => return ran,
+        }
+    }
+    max_tasks
+}
+
+fn next_task(inner: &HandleInner) -> Option<Task> {
+    Priority::ALL
+        .iter()
+        .find_map(|&priority| inner.pop_task(priority, None))
+}
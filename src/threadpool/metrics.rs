@@ -0,0 +1,98 @@
+use std::{
+    sync::atomic::{AtomicU32, AtomicU64, Ordering},
+    time::Duration,
+};
+
+use super::{Handle, Priority};
+
+#[derive(Debug, Clone, Default)]
+pub struct Metrics {
+    pub tasks_spawned: u64,
+    pub tasks_completed: u64,
+    pub tasks_panicked: u64,
+    pub tasks_cancelled: u64,
+    pub high_queue_depth: usize,
+    pub normal_queue_depth: usize,
+    pub low_queue_depth: usize,
+    pub polls: u64,
+    pub poll_time: Duration,
+    #[cfg(all(feature = "io", feature = "io-shared"))]
+    pub io_handles: usize,
+    #[cfg(all(feature = "io", feature = "io-shared"))]
+    pub pending_reads: usize,
+    #[cfg(all(feature = "io", feature = "io-shared"))]
+    pub pending_writes: usize,
+    pub min_threads: u32,
+    pub max_threads: u32,
+}
+
+#[derive(Default)]
+pub(crate) struct MetricsInner {
+    pub(crate) tasks_spawned: AtomicU64,
+    pub(crate) tasks_completed: AtomicU64,
+    pub(crate) tasks_panicked: AtomicU64,
+    pub(crate) tasks_cancelled: AtomicU64,
+    pub(crate) polls: AtomicU64,
+    pub(crate) poll_nanos: AtomicU64,
+    pub(crate) min_threads: AtomicU32,
+    pub(crate) max_threads: AtomicU32,
+}
+
+impl MetricsInner {
+    pub(crate) fn new(min_threads: u32, max_threads: u32) -> Self {
+        Self {
+            min_threads: AtomicU32::new(min_threads),
+            max_threads: AtomicU32::new(max_threads),
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn task_polled(&self, duration: Duration) {
+        self.polls.fetch_add(1, Ordering::Relaxed);
+        self.poll_nanos
+            .fetch_add(duration.as_nanos() as u64, Ordering::Relaxed);
+    }
+}
+
+impl Handle {
+    pub fn metrics(&self) -> Metrics {
+        let inner = &self.inner;
+        let metrics = &inner.metrics;
+
+        #[cfg_attr(not(all(feature = "io", feature = "io-shared")), allow(unused_mut))]
+        let mut snapshot = Metrics {
+            tasks_spawned: metrics.tasks_spawned.load(Ordering::Relaxed),
+            tasks_completed: metrics.tasks_completed.load(Ordering::Relaxed),
+            tasks_panicked: metrics.tasks_panicked.load(Ordering::Relaxed),
+            tasks_cancelled: metrics.tasks_cancelled.load(Ordering::Relaxed),
+            high_queue_depth: inner.queue_depth(Priority::High),
+            normal_queue_depth: inner.queue_depth(Priority::Normal),
+            low_queue_depth: inner.queue_depth(Priority::Low),
+            polls: metrics.polls.load(Ordering::Relaxed),
+            poll_time: Duration::from_nanos(metrics.poll_nanos.load(Ordering::Relaxed)),
+            min_threads: metrics.min_threads.load(Ordering::Relaxed),
+            max_threads: metrics.max_threads.load(Ordering::Relaxed),
+            ..Default::default()
+        };
+
+        #[cfg(all(feature = "io", feature = "io-shared"))]
+        {
+            let registry = inner.io.lock().unwrap().clone();
+            for io in registry.iter().filter_map(|io| io.upgrade()) {
+                snapshot.io_handles += 1;
+                if io.is_reading() {
+                    snapshot.pending_reads += 1;
+                }
+                if io.is_writing() {
+                    snapshot.pending_writes += 1;
+                }
+            }
+        }
+
+        snapshot
+    }
+
+    pub(crate) fn task_metrics(&self) -> &MetricsInner {
+        &self.inner.metrics
+    }
+}
@@ -0,0 +1,767 @@
+mod backpressure;
+mod env;
+mod hooks;
+mod idle;
+mod local;
+#[cfg(feature = "metrics")]
+mod metrics;
+mod scaler;
+mod shutdown;
+mod watchdog;
+mod weak;
+mod worker;
+
+#[cfg(feature = "console")]
+use std::net::SocketAddr;
+#[cfg(all(feature = "io", feature = "io-shared"))]
+use std::sync::Weak;
+use std::{
+    cmp::Ordering,
+    ffi::c_void,
+    fmt, io, mem,
+    ops::Deref,
+    ptr,
+    sync::{
+        atomic::{self, AtomicBool, AtomicUsize},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use winapi::{
+    shared::minwindef::{FALSE, TRUE},
+    um::{
+        sysinfoapi::{GetSystemInfo, SYSTEM_INFO},
+        threadpoolapiset::{
+            CloseThreadpool, CloseThreadpoolCleanupGroup, CloseThreadpoolCleanupGroupMembers,
+            CreateThreadpool, CreateThreadpoolCleanupGroup, CreateThreadpoolWork,
+            QueryThreadpoolStackInformation, SetThreadpoolStackInformation,
+            SetThreadpoolThreadMaximum, SetThreadpoolThreadMinimum,
+        },
+        winnt::{
+            TP_CALLBACK_ENVIRON_V3_u, PTP_WORK, TP_CALLBACK_ENVIRON_V3, TP_CALLBACK_PRIORITY_HIGH,
+            TP_CALLBACK_PRIORITY_LOW, TP_CALLBACK_PRIORITY_NORMAL, TP_POOL_STACK_INFORMATION,
+        },
+    },
+};
+
+use concurrent_queue::ConcurrentQueue;
+
+pub use crate::context::ContextGuard;
+use backpressure::Waiters;
+pub(crate) use hooks::Hooks;
+use idle::IdleWaiters;
+pub use idle::WaitIdle;
+use local::Driver;
+#[cfg(feature = "metrics")]
+pub use metrics::Metrics;
+#[cfg(feature = "metrics")]
+pub(crate) use metrics::MetricsInner;
+use scaler::{Scaler, ScalerState};
+pub(crate) use shutdown::TaskGuard;
+use watchdog::Watchdog;
+pub use watchdog::WatchdogEvent;
+pub use weak::WeakHandle;
+pub(crate) use worker::{run_tasks, schedule};
+use worker::{Task, Workers};
+
+#[cfg(feature = "console")]
+use crate::console::Console;
+#[cfg(all(feature = "io", feature = "io-shared"))]
+use crate::io::shared::IoHandle;
+#[cfg(feature = "sim")]
+use crate::net::sim::Network;
+use crate::task::TaskRegistry;
+#[cfg(feature = "time")]
+use crate::time::Clock;
+
+#[derive(Debug)]
+pub struct Threadpool {
+    handle: Handle,
+    #[cfg(feature = "console")]
+    console_server: Option<crate::task::JoinHandle<()>>,
+}
+
+#[derive(Clone)]
+pub struct Handle {
+    inner: Arc<HandleInner>,
+    priority: Priority,
+    #[cfg(feature = "tracing")]
+    pub(crate) span: Option<tracing::Span>,
+}
+
+/// Configures and builds a [`Threadpool`].
+///
+/// `Builder` is `Clone` but not `Copy` since it holds the hooks and callbacks it's given, which
+/// are shared between clones.
+#[derive(Debug, Clone)]
+pub struct Builder {
+    max_threads: u32,
+    min_threads: u32,
+    stack_reserve: Option<usize>,
+    stack_commit: Option<usize>,
+    max_queued_tasks: [usize; 3],
+    hooks: Arc<Hooks>,
+    watchdog: Watchdog,
+    scaler: Option<Scaler>,
+    current_thread: bool,
+    seed: Option<u64>,
+    priority: Priority,
+    #[cfg(feature = "time")]
+    start_paused: bool,
+    #[cfg(feature = "console")]
+    console: Option<SocketAddr>,
+    #[cfg(feature = "net")]
+    net: bool,
+    #[cfg(feature = "sim")]
+    network: Option<Network>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u32)]
+pub enum Priority {
+    High = TP_CALLBACK_PRIORITY_HIGH,
+    Normal = TP_CALLBACK_PRIORITY_NORMAL,
+    Low = TP_CALLBACK_PRIORITY_LOW,
+}
+
+struct HandleInner {
+    high_queue: TaskQueue,
+    normal_queue: TaskQueue,
+    low_queue: TaskQueue,
+    workers: Workers,
+    callback_environ: TP_CALLBACK_ENVIRON_V3,
+    hooks: Arc<Hooks>,
+    registry: TaskRegistry,
+    idle_waiters: IdleWaiters,
+    closed: AtomicBool,
+    cancelled: AtomicBool,
+    tasks: AtomicUsize,
+    scaler: ScalerState,
+    driver: Option<Driver>,
+    #[cfg(feature = "time")]
+    clock: Clock,
+    #[cfg(feature = "sim")]
+    network: Option<Network>,
+    #[cfg(all(feature = "io", feature = "io-shared"))]
+    io: Mutex<Vec<Weak<IoHandle>>>,
+    /// IO events waiting for their operation to complete.
+    #[cfg(all(feature = "io", feature = "io-shared"))]
+    io_events: AtomicUsize,
+    #[cfg(feature = "metrics")]
+    metrics: MetricsInner,
+    #[cfg(feature = "console")]
+    console: Option<Arc<Console>>,
+}
+
+unsafe impl Send for HandleInner {}
+unsafe impl Sync for HandleInner {}
+
+struct TaskQueue {
+    queue: ConcurrentQueue<Task>,
+    work: PTP_WORK,
+    /// Work submissions no callback has started handling yet.
+    idle: AtomicUsize,
+    /// Tasks waiting in the pool-wide queue or in a worker's local queue.
+    queued: AtomicUsize,
+    limit: usize,
+    waiters: Mutex<Waiters>,
+    waiting: AtomicUsize,
+}
+
+impl Threadpool {
+    pub fn new() -> io::Result<Threadpool> {
+        Builder::default().build()
+    }
+
+    pub fn builder() -> Builder {
+        Builder::default()
+    }
+}
+
+impl Handle {
+    #[cfg(any(feature = "net", feature = "time"))]
+    pub(crate) fn callback_environ(&self) -> TP_CALLBACK_ENVIRON_V3 {
+        let mut ce = self.inner.callback_environ;
+        ce.CallbackPriority = self.priority as u32;
+        ce
+    }
+
+    pub(crate) fn hooks(&self) -> &Arc<Hooks> {
+        &self.inner.hooks
+    }
+
+    #[cfg(feature = "console")]
+    pub(crate) fn console(&self) -> Option<&Console> {
+        self.inner.console.as_deref()
+    }
+
+    #[cfg(feature = "time")]
+    pub(crate) fn clock(&self) -> &Clock {
+        &self.inner.clock
+    }
+
+    #[cfg(feature = "sim")]
+    pub(crate) fn network(&self) -> Option<&Network> {
+        self.inner.network.as_ref()
+    }
+
+    pub(crate) fn registry(&self) -> &TaskRegistry {
+        &self.inner.registry
+    }
+
+    pub(crate) fn driver(&self) -> Option<&Driver> {
+        self.inner.driver.as_ref()
+    }
+
+    pub(crate) fn blocking_started(&self) {
+        self.inner.scaler.blocking_started()
+    }
+
+    pub(crate) fn blocking_ended(&self) {
+        self.inner.scaler.blocking_ended()
+    }
+
+    pub(crate) fn is_closed(&self) -> bool {
+        self.inner.closed.load(atomic::Ordering::Acquire)
+    }
+
+    #[cfg(all(feature = "io", feature = "io-shared"))]
+    pub(crate) fn register_io(&self, io: &Arc<IoHandle>) {
+        let mut registry = self.inner.io.lock().unwrap();
+        if registry.len() == registry.capacity() {
+            registry.retain(|io| io.strong_count() > 0);
+        }
+        registry.push(Arc::downgrade(io));
+    }
+
+    #[cfg(all(feature = "io", feature = "io-shared"))]
+    pub(crate) fn io_event_started(&self) {
+        self.inner.io_events.fetch_add(1, atomic::Ordering::AcqRel);
+    }
+
+    pub fn set_max_threads(&self, maximum: u32) -> &Self {
+        unsafe { SetThreadpoolThreadMaximum(self.inner.callback_environ.Pool, maximum) }
+        self.inner.scaler.set_max_threads(maximum);
+        #[cfg(feature = "metrics")]
+        self.inner
+            .metrics
+            .max_threads
+            .store(maximum, atomic::Ordering::Relaxed);
+        self
+    }
+
+    pub fn set_min_threads(&self, minimum: u32) -> &Self {
+        self.try_set_min_threads(minimum).unwrap()
+    }
+
+    pub fn try_set_min_threads(&self, minimum: u32) -> io::Result<&Self> {
+        if unsafe { SetThreadpoolThreadMinimum(self.inner.callback_environ.Pool, minimum) } == TRUE
+        {
+            self.inner.scaler.set_min_threads(minimum);
+            #[cfg(feature = "metrics")]
+            self.inner
+                .metrics
+                .min_threads
+                .store(minimum, atomic::Ordering::Relaxed);
+            Ok(self)
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+
+    pub fn priority(&self) -> Priority {
+        self.priority
+    }
+
+    pub fn set_priority(&mut self, priority: Priority) -> &mut Self {
+        self.priority = priority;
+        self
+    }
+}
+
+impl Builder {
+    pub fn new() -> Builder {
+        let mut system_info = SYSTEM_INFO::default();
+        unsafe { GetSystemInfo(&mut system_info) };
+
+        Self {
+            max_threads: 512,
+            min_threads: system_info.dwNumberOfProcessors,
+            stack_reserve: None,
+            stack_commit: None,
+            max_queued_tasks: [usize::MAX; 3],
+            hooks: Arc::default(),
+            watchdog: Watchdog::default(),
+            scaler: None,
+            current_thread: false,
+            seed: None,
+            priority: Priority::Normal,
+            #[cfg(feature = "time")]
+            start_paused: false,
+            #[cfg(feature = "console")]
+            console: None,
+            #[cfg(feature = "net")]
+            net: true,
+            #[cfg(feature = "sim")]
+            network: None,
+        }
+    }
+
+    pub fn min_threads(mut self, max: u32) -> Builder {
+        self.max_threads = max;
+        self
+    }
+
+    pub fn max_threads(mut self, min: u32) -> Builder {
+        self.min_threads = min;
+        self
+    }
+
+    pub fn stack_reserve(mut self, size: usize) -> Builder {
+        self.stack_reserve = Some(size);
+        self
+    }
+
+    pub fn stack_commit(mut self, size: usize) -> Builder {
+        self.stack_commit = Some(size);
+        self
+    }
+
+    /// Limits how many tasks can be queued at every priority before [`Handle::try_spawn`] fails
+    /// and [`Handle::spawn_with_backpressure`] waits for room.
+    ///
+    /// The limit is only checked when spawning, [`Handle::spawn`] ignores it and tasks that are
+    /// woken up are always queued.
+    pub fn max_queued_tasks(mut self, limit: usize) -> Builder {
+        self.max_queued_tasks = [limit; 3];
+        self
+    }
+
+    /// Limits how many tasks can be queued at `priority`, see [`Builder::max_queued_tasks`].
+    pub fn max_queued_tasks_for(mut self, priority: Priority, limit: usize) -> Builder {
+        self.max_queued_tasks[priority.index()] = limit;
+        self
+    }
+
+    /// Runs `f` on each pool thread before it runs its first task or IO callback.
+    pub fn on_thread_start<F>(mut self, f: F) -> Builder
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        Arc::make_mut(&mut self.hooks).on_thread_start = Some(Arc::new(f));
+        self
+    }
+
+    /// Runs `f` on each pool thread that ran a task or an IO callback when it exits.
+    ///
+    /// Threads exit when the system shrinks the pool or some time after it was closed, so `f`
+    /// can run well after the pool was dropped, or not at all if the process exits first.
+    pub fn on_thread_stop<F>(mut self, f: F) -> Builder
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        Arc::make_mut(&mut self.hooks).on_thread_stop = Some(Arc::new(f));
+        self
+    }
+
+    pub fn on_task_poll_start<F>(mut self, f: F) -> Builder
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        Arc::make_mut(&mut self.hooks).on_task_poll_start = Some(Arc::new(f));
+        self
+    }
+
+    pub fn on_task_poll_end<F>(mut self, f: F) -> Builder
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        Arc::make_mut(&mut self.hooks).on_task_poll_end = Some(Arc::new(f));
+        self
+    }
+
+    /// Makes this a [`Builder::current_thread`] pool that runs a ready task picked at random with
+    /// `seed` instead of the oldest one, so a given seed replays the same interleaving of tasks.
+    ///
+    /// A panicking [`Handle::block_on`] prints the seed. Wakeups coming from IO completions or
+    /// other threads still happen in whatever order they happen.
+    pub fn deterministic(mut self, seed: u64) -> Builder {
+        self.current_thread = true;
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Runs `f` when a task of a [`Builder::current_thread`] pool gets queued while the pool had
+    /// no wakeup pending, so a host event loop knows to call [`Handle::drive`]. It can be called
+    /// from any thread.
+    pub fn on_wakeup<F>(mut self, f: F) -> Builder
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        Arc::make_mut(&mut self.hooks).on_wakeup = Some(Arc::new(f));
+        self
+    }
+
+    /// Reports task polls that take longer than `threshold`, which usually means the task is
+    /// blocking a worker thread.
+    ///
+    /// Events go to [`Builder::on_watchdog_event`], or are logged as `tracing` warnings with the
+    /// `tracing` feature. Nothing is checked when neither is available.
+    pub fn slow_poll_threshold(mut self, threshold: Duration) -> Builder {
+        self.watchdog.slow_poll = Some(threshold);
+        self
+    }
+
+    /// Reports tasks that have been pending without being woken up for longer than `threshold`.
+    pub fn stalled_task_threshold(mut self, threshold: Duration) -> Builder {
+        self.watchdog.stalled = Some(threshold);
+        self
+    }
+
+    /// Sets the callback watchdog events are reported to instead of `tracing`.
+    pub fn on_watchdog_event<F>(mut self, f: F) -> Builder
+    where
+        F: Fn(&WatchdogEvent) + Send + Sync + 'static,
+    {
+        self.watchdog.callback = Some(Arc::new(f));
+        self
+    }
+
+    /// Lets a controller adjust the thread count between `min` and `max` from the queue depth,
+    /// how long tasks wait in the queue and how many callbacks are blocking. This overrides the
+    /// fixed thread counts.
+    pub fn adaptive_threads(mut self, min: u32, max: u32) -> Builder {
+        self.scaler = Some(Scaler {
+            min,
+            max: max.max(min),
+        });
+        self
+    }
+
+    /// Runs every task on the thread calling [`Handle::block_on`] instead of the pool's threads.
+    ///
+    /// The pool only keeps a single thread to receive IO completions, and the thread counts and
+    /// [`Builder::adaptive_threads`] are ignored.
+    pub fn current_thread(mut self) -> Builder {
+        self.current_thread = true;
+        self
+    }
+
+    /// Sets the priority of the pool's handle, which tasks spawned through it inherit.
+    pub fn priority(mut self, priority: Priority) -> Builder {
+        self.priority = priority;
+        self
+    }
+
+    /// Starts the pool with its clock paused, see [`crate::time::pause`].
+    ///
+    /// Only [`Builder::current_thread`] pools can start paused, as they're the ones skipping
+    /// ahead to the next timer while no task is ready. Building any other pool fails.
+    #[cfg(feature = "time")]
+    pub fn start_paused(mut self, paused: bool) -> Builder {
+        self.start_paused = paused;
+        self
+    }
+
+    /// Serves task events to `wae-console` clients connecting to `addr`.
+    ///
+    /// The server stops when the [`Threadpool`] is shut down or dropped, and isn't counted among
+    /// the pool's tasks.
+    #[cfg(feature = "console")]
+    pub fn console(mut self, addr: SocketAddr) -> Builder {
+        self.console = Some(addr);
+        self
+    }
+
+    #[cfg(feature = "net")]
+    pub fn net(mut self, enabled: bool) -> Builder {
+        self.net = enabled;
+        self
+    }
+
+    /// Makes TCP sockets and name resolution go through the simulated `network`, see
+    /// [`crate::net::sim`].
+    #[cfg(feature = "sim")]
+    pub fn network(mut self, network: Network) -> Builder {
+        self.network = Some(network);
+        self
+    }
+
+    pub fn build(mut self) -> io::Result<Threadpool> {
+        #[cfg(feature = "time")]
+        if self.start_paused && !self.current_thread {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "only current thread pools can start paused, nothing would advance the clock",
+            ));
+        }
+        if self.current_thread {
+            self.scaler = None;
+            self.min_threads = 1;
+            self.max_threads = 1;
+        }
+        if let Some(scaler) = self.scaler {
+            self.min_threads = scaler.min;
+            self.max_threads = scaler.max;
+        }
+
+        let pool = unsafe { CreateThreadpool(ptr::null_mut()) };
+        if pool.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+
+        unsafe { SetThreadpoolThreadMaximum(pool, self.max_threads) };
+        if unsafe { SetThreadpoolThreadMinimum(pool, self.min_threads) } == FALSE {
+            unsafe { CloseThreadpool(pool) };
+            return Err(io::Error::last_os_error());
+        }
+
+        if self.stack_reserve.is_some() || self.stack_commit.is_some() {
+            let mut stack_information = TP_POOL_STACK_INFORMATION::default();
+            if unsafe { QueryThreadpoolStackInformation(pool, &mut stack_information) } == FALSE {
+                unsafe { CloseThreadpool(pool) };
+                return Err(io::Error::last_os_error());
+            }
+
+            if let Some(reserve) = self.stack_reserve {
+                stack_information.StackReserve = reserve;
+            }
+            if let Some(commit) = self.stack_commit {
+                stack_information.StackCommit = commit;
+            }
+            if unsafe { SetThreadpoolStackInformation(pool, &mut stack_information) } == FALSE {
+                unsafe { CloseThreadpool(pool) };
+                return Err(io::Error::last_os_error());
+            }
+        }
+
+        let cleanup_group = unsafe { CreateThreadpoolCleanupGroup() };
+        if cleanup_group.is_null() {
+            unsafe { CloseThreadpool(pool) };
+            return Err(io::Error::last_os_error());
+        }
+
+        let mut callback_environ = TP_CALLBACK_ENVIRON_V3 {
+            Version: 3,
+            Pool: pool,
+            CleanupGroup: cleanup_group,
+            CleanupGroupCancelCallback: None,
+            RaceDll: ptr::null_mut(),
+            ActivationContext: ptr::null_mut(),
+            FinalizationCallback: None,
+            u: TP_CALLBACK_ENVIRON_V3_u::default(),
+            CallbackPriority: Priority::default() as u32,
+            Size: mem::size_of::<TP_CALLBACK_ENVIRON_V3>() as u32,
+        };
+
+        let mut inner = Arc::new(HandleInner {
+            high_queue: TaskQueue::new(self.max_queued_tasks[Priority::High.index()]),
+            normal_queue: TaskQueue::new(self.max_queued_tasks[Priority::Normal.index()]),
+            low_queue: TaskQueue::new(self.max_queued_tasks[Priority::Low.index()]),
+            workers: Workers::default(),
+            callback_environ,
+            hooks: self.hooks.clone(),
+            registry: TaskRegistry::new(),
+            idle_waiters: IdleWaiters::default(),
+            closed: AtomicBool::new(false),
+            cancelled: AtomicBool::new(false),
+            tasks: AtomicUsize::new(0),
+            scaler: ScalerState::new(self.scaler, self.min_threads, self.max_threads),
+            driver: self
+                .current_thread
+                .then(|| Driver::new(self.hooks.on_wakeup.clone(), self.seed)),
+            #[cfg(feature = "time")]
+            clock: Clock::new(self.start_paused),
+            #[cfg(feature = "sim")]
+            network: self.network.clone(),
+            #[cfg(all(feature = "io", feature = "io-shared"))]
+            io: Mutex::new(Vec::new()),
+            #[cfg(all(feature = "io", feature = "io-shared"))]
+            io_events: AtomicUsize::new(0),
+            #[cfg(feature = "metrics")]
+            metrics: MetricsInner::new(self.min_threads, self.max_threads),
+            #[cfg(feature = "console")]
+            console: self.console.map(|_| Arc::new(Console::new())),
+        });
+        let inner_mut = Arc::get_mut(&mut inner).unwrap();
+        let context = inner_mut as *const HandleInner;
+
+        inner_mut.high_queue.work =
+            Self::create_work(Priority::High, context, &mut callback_environ)?;
+        inner_mut.normal_queue.work =
+            Self::create_work(Priority::Normal, context, &mut callback_environ)?;
+        inner_mut.low_queue.work =
+            Self::create_work(Priority::Low, context, &mut callback_environ)?;
+
+        #[cfg(feature = "net")]
+        if self.net {
+            let mut wsadata = winapi::um::winsock2::WSADATA::default();
+            let ret = unsafe { winapi::um::winsock2::WSAStartup(0x0202, &mut wsadata) };
+            if ret != 0 {
+                unsafe { CloseThreadpool(pool) };
+                return Err(io::Error::from_raw_os_error(ret));
+            }
+        }
+
+        self.watchdog.start(Arc::downgrade(&inner))?;
+        if let Some(scaler) = self.scaler {
+            scaler.start(Arc::downgrade(&inner))?;
+        }
+
+        #[cfg_attr(not(feature = "console"), allow(unused_mut))]
+        let mut threadpool = Threadpool {
+            handle: Handle {
+                inner,
+                priority: self.priority,
+                #[cfg(feature = "tracing")]
+                span: None,
+            },
+            #[cfg(feature = "console")]
+            console_server: None,
+        };
+
+        #[cfg(feature = "console")]
+        if let (Some(addr), Some(console)) = (self.console, &threadpool.inner.console) {
+            let listener = threadpool.block_on(crate::net::TcpListener::bind(addr))?;
+            let console = console.clone();
+            threadpool.console_server =
+                Some(threadpool.spawn_internal("wae-console", async move {
+                    console.serve(listener).await;
+                }));
+        }
+
+        Ok(threadpool)
+    }
+
+    fn create_work(
+        priority: Priority,
+        context: *const HandleInner,
+        callback_environ: &mut TP_CALLBACK_ENVIRON_V3,
+    ) -> io::Result<PTP_WORK> {
+        callback_environ.CallbackPriority = priority as u32;
+        let work = unsafe {
+            CreateThreadpoolWork(
+                Some(crate::task::callback),
+                context as *mut c_void,
+                callback_environ,
+            )
+        };
+        if !work.is_null() {
+            Ok(work)
+        } else {
+            unsafe {
+                CloseThreadpoolCleanupGroupMembers(
+                    callback_environ.CleanupGroup,
+                    TRUE,
+                    ptr::null_mut(),
+                );
+                CloseThreadpoolCleanupGroup(callback_environ.CleanupGroup);
+                CloseThreadpool(callback_environ.Pool);
+            }
+            Err(io::Error::last_os_error())
+        }
+    }
+}
+
+impl TaskQueue {
+    fn new(limit: usize) -> Self {
+        Self {
+            queue: ConcurrentQueue::unbounded(),
+            work: ptr::null_mut(),
+            idle: AtomicUsize::new(0),
+            queued: AtomicUsize::new(0),
+            limit,
+            waiters: Mutex::default(),
+            waiting: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl HandleInner {
+    fn queue(&self, priority: Priority) -> &TaskQueue {
+        match priority {
+            Priority::High => &self.high_queue,
+            Priority::Normal => &self.normal_queue,
+            Priority::Low => &self.low_queue,
+        }
+    }
+}
+
+impl Deref for Threadpool {
+    type Target = Handle;
+
+    fn deref(&self) -> &Self::Target {
+        &self.handle
+    }
+}
+
+impl fmt::Debug for Handle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Handle")
+            .field("pool", &self.inner.callback_environ.Pool)
+            .field("priority", &self.priority)
+            .finish()
+    }
+}
+
+#[cfg(feature = "console")]
+impl Drop for Threadpool {
+    fn drop(&mut self) {
+        let inner = &self.handle.inner;
+        if let (Some(console), Some(server)) = (&inner.console, &self.console_server) {
+            console.close();
+            // The server keeps the pool alive until it ends, and nothing else would run it on a
+            // current thread pool.
+            if let Some(driver) = &inner.driver {
+                while !server.is_finished() && driver.try_run(inner, TaskQueue::MAX_BATCH) > 0 {}
+            }
+        }
+    }
+}
+
+impl Drop for HandleInner {
+    fn drop(&mut self) {
+        unsafe {
+            CloseThreadpoolCleanupGroupMembers(
+                self.callback_environ.CleanupGroup,
+                TRUE,
+                ptr::null_mut(),
+            );
+            CloseThreadpoolCleanupGroup(self.callback_environ.CleanupGroup);
+            CloseThreadpool(self.callback_environ.Pool);
+        }
+    }
+}
+
+impl Default for Builder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Default for Priority {
+    fn default() -> Self {
+        Self::Normal
+    }
+}
+
+macro_rules! priority_ord {
+    ($self:expr, $other:expr) => {
+        match ($self as u32).cmp(&($other as u32)) {
+            Ordering::Less => Ordering::Greater,
+            Ordering::Equal => Ordering::Equal,
+            Ordering::Greater => Ordering::Less,
+        }
+    };
+}
+impl PartialOrd for Priority {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(priority_ord!(*self, *other))
+    }
+}
+impl Ord for Priority {
+    fn cmp(&self, other: &Self) -> Ordering {
+        priority_ord!(*self, *other)
+    }
+}
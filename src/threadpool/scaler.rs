@@ -0,0 +1,162 @@
+use std::{
+    io,
+    sync::{
+        atomic::{AtomicU32, AtomicU64, AtomicUsize, Ordering},
+        Arc, Weak,
+    },
+    thread,
+    time::Duration,
+};
+
+use super::{Handle, HandleInner, Priority};
+
+/// Bounds the thread count controller keeps the pool within.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Scaler {
+    pub(crate) min: u32,
+    pub(crate) max: u32,
+}
+
+pub(crate) struct ScalerState {
+    bounds: Option<Scaler>,
+    min_threads: AtomicU32,
+    max_threads: AtomicU32,
+    wait_nanos: AtomicU64,
+    waits: AtomicU64,
+    blocking: AtomicUsize,
+}
+
+impl Scaler {
+    const SAMPLE_INTERVAL: Duration = Duration::from_millis(100);
+    /// Average time tasks spend queued above which the pool is considered starved for threads.
+    const WAIT_THRESHOLD: u64 = 1_000_000;
+
+    pub(crate) fn start(self, inner: Weak<HandleInner>) -> io::Result<()> {
+        thread::Builder::new()
+            .name("wae-scaler".to_owned())
+            .spawn(move || loop {
+                thread::sleep(Self::SAMPLE_INTERVAL);
+                let inner = match inner.upgrade() {
+                    Some(inner) => inner,
+                    None => return,
+                };
+                if inner.closed.load(Ordering::Acquire) {
+                    return;
+                }
+                self.sample(inner);
+            })
+            .map(drop)
+    }
+
+    fn sample(&self, inner: Arc<HandleInner>) {
+        let state = &inner.scaler;
+        let queued: usize = Priority::ALL
+            .iter()
+            .map(|&priority| inner.queue(priority).queued.load(Ordering::Relaxed))
+            .sum();
+        let wait_nanos = state.wait_nanos.swap(0, Ordering::Relaxed);
+        let waits = state.waits.swap(0, Ordering::Relaxed);
+        let average_wait = wait_nanos / waits.max(1);
+        let blocking = state.blocking.load(Ordering::Relaxed) as u32;
+
+        let current = state.min_threads.load(Ordering::Relaxed);
+        let target = if queued > 0 && average_wait > Self::WAIT_THRESHOLD {
+            current + (current / 4).max(1)
+        } else if queued == 0 && average_wait < Self::WAIT_THRESHOLD / 4 {
+            current.saturating_sub(1)
+        } else {
+            current
+        };
+        // Threads stuck in blocking callbacks don't help with the queue.
+        let target = target.max(self.min + blocking).clamp(self.min, self.max);
+        let maximum = (target * 2 + blocking).clamp(target, self.max);
+        if target == current && maximum == state.max_threads.load(Ordering::Relaxed) {
+            return;
+        }
+
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            queued,
+            average_wait,
+            blocking,
+            min_threads = target,
+            max_threads = maximum,
+            "scaling thread pool"
+        );
+        let handle = Handle {
+            inner,
+            priority: Priority::Normal,
+            #[cfg(feature = "tracing")]
+            span: None,
+        };
+        handle.resize(target, maximum).ok();
+    }
+}
+
+impl ScalerState {
+    pub(crate) fn new(bounds: Option<Scaler>, min_threads: u32, max_threads: u32) -> Self {
+        Self {
+            bounds,
+            min_threads: AtomicU32::new(min_threads),
+            max_threads: AtomicU32::new(max_threads),
+            wait_nanos: AtomicU64::new(0),
+            waits: AtomicU64::new(0),
+            blocking: AtomicUsize::new(0),
+        }
+    }
+
+    pub(crate) fn set_min_threads(&self, minimum: u32) {
+        self.min_threads.store(minimum, Ordering::Relaxed)
+    }
+
+    pub(crate) fn set_max_threads(&self, maximum: u32) {
+        self.max_threads.store(maximum, Ordering::Relaxed)
+    }
+
+    /// Records how long a task spent queued before getting polled.
+    pub(crate) fn task_waited(&self, nanos: u64) {
+        if self.bounds.is_some() {
+            self.wait_nanos.fetch_add(nanos, Ordering::Relaxed);
+            self.waits.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn blocking_started(&self) {
+        self.blocking.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn blocking_ended(&self) {
+        self.blocking.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl Handle {
+    /// Raises the minimum thread count to `threads` ahead of a known burst of work.
+    ///
+    /// With [`Builder::adaptive_threads`](super::Builder::adaptive_threads) the hint is clamped
+    /// to the controller's bounds and decays once the pool isn't busy anymore.
+    pub fn scale_hint(&self, threads: u32) -> io::Result<()> {
+        let state = &self.inner.scaler;
+        let threads = match state.bounds {
+            Some(bounds) => threads.clamp(bounds.min, bounds.max),
+            None => threads,
+        };
+        if threads <= state.min_threads.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+        let maximum = state.max_threads.load(Ordering::Relaxed).max(threads);
+        self.resize(threads, maximum)
+    }
+
+    /// Sets both thread counts in an order that keeps the minimum below the maximum.
+    fn resize(&self, minimum: u32, maximum: u32) -> io::Result<()> {
+        if minimum > self.inner.scaler.min_threads.load(Ordering::Relaxed) {
+            self.set_max_threads(maximum);
+            self.try_set_min_threads(minimum)?;
+        } else {
+            self.try_set_min_threads(minimum)?;
+            self.set_max_threads(maximum);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,235 @@
+use std::{
+    ffi::c_void,
+    mem,
+    panic::Location,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use winapi::um::synchapi::{WaitOnAddress, WakeByAddressAll};
+
+use super::{HandleInner, Priority, TaskQueue, Threadpool};
+#[cfg(feature = "console")]
+use crate::console::Event;
+use crate::{
+    task::{PollStart, TaskInfo},
+    threadpool::Handle,
+};
+
+pub(crate) struct TaskGuard {
+    inner: Arc<HandleInner>,
+    info: Arc<TaskInfo>,
+    #[cfg(feature = "metrics")]
+    completed: bool,
+}
+
+impl Threadpool {
+    const SHUTDOWN_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+    /// Stops accepting new tasks and waits for running tasks and pending IO to finish.
+    ///
+    /// Whatever is still alive once `deadline` is reached gets cancelled: queued tasks are dropped
+    /// right away and the others the next time they're woken up, as nothing else owns them. The
+    /// number of tasks still alive at the deadline is returned.
+    pub fn shutdown(self, deadline: Instant) -> usize {
+        let inner = &self.handle.inner;
+        inner.closed.store(true, Ordering::Release);
+        for priority in Priority::ALL.iter().copied() {
+            inner.queue(priority).wake_waiters();
+        }
+        #[cfg(feature = "console")]
+        if let Some(console) = &inner.console {
+            console.close();
+        }
+
+        loop {
+            let tasks = inner.tasks.load(Ordering::Acquire);
+            if tasks == 0 && !inner.io_pending() {
+                // Nothing is left to finish, IO started from now on fails right away.
+                inner.cancelled.store(true, Ordering::Release);
+                return 0;
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+            if let Some(driver) = &inner.driver {
+                if driver.try_run(inner, TaskQueue::MAX_BATCH) > 0 {
+                    continue;
+                }
+            }
+
+            let timeout = (deadline - now).min(Self::SHUTDOWN_POLL_INTERVAL);
+            unsafe {
+                WaitOnAddress(
+                    &inner.tasks as *const AtomicUsize as *mut c_void,
+                    &tasks as *const usize as *mut c_void,
+                    mem::size_of::<usize>(),
+                    timeout.as_millis() as u32,
+                );
+            }
+        }
+
+        inner.cancelled.store(true, Ordering::Release);
+        let alive = inner.tasks.load(Ordering::Acquire);
+
+        #[cfg(all(feature = "io", feature = "io-shared"))]
+        {
+            let registry = inner.io.lock().unwrap().clone();
+            for io in registry.iter().filter_map(|io| io.upgrade()) {
+                unsafe {
+                    io.cancel_read(false).ok();
+                    io.cancel_write(false).ok();
+                }
+            }
+        }
+
+        if let Some(driver) = &inner.driver {
+            driver.clear();
+        }
+        let workers = inner.workers.snapshot();
+        for priority in Priority::ALL.iter().copied() {
+            let queue = inner.queue(priority);
+            while queue.queue.pop().is_ok() {}
+            for worker in &workers {
+                while worker.pop(priority).is_some() {}
+            }
+            queue.queued.store(0, Ordering::SeqCst);
+        }
+
+        alive
+    }
+}
+
+impl Handle {
+    /// Tracks an internal task, which shutdown doesn't wait for and the console doesn't report.
+    #[cfg(feature = "console")]
+    pub(crate) fn internal_task_guard(
+        &self,
+        name: Option<String>,
+        location: &'static Location<'static>,
+    ) -> TaskGuard {
+        TaskGuard {
+            inner: self.inner.clone(),
+            info: self.inner.registry.internal(name, location, self.priority),
+            #[cfg(feature = "metrics")]
+            completed: false,
+        }
+    }
+
+    pub(crate) fn task_guard(
+        &self,
+        name: Option<String>,
+        location: &'static Location<'static>,
+    ) -> TaskGuard {
+        let info = self.inner.registry.insert(name, location, self.priority);
+        self.inner.tasks.fetch_add(1, Ordering::AcqRel);
+        #[cfg(feature = "metrics")]
+        self.inner
+            .metrics
+            .tasks_spawned
+            .fetch_add(1, Ordering::Relaxed);
+        #[cfg(feature = "console")]
+        if let Some(console) = self.console() {
+            console.emit(Event::Spawn {
+                id: info.id,
+                location,
+                name: info.name.clone(),
+            });
+        }
+        TaskGuard {
+            inner: self.inner.clone(),
+            info,
+            #[cfg(feature = "metrics")]
+            completed: false,
+        }
+    }
+}
+
+impl Handle {
+    /// Marks the task as running.
+    pub(crate) fn poll_started(&self, info: &TaskInfo) -> PollStart {
+        let (start, wait) = info.set_running(self.inner.registry.now());
+        if let Some(wait) = wait {
+            self.inner.scaler.task_waited(wait);
+        }
+        start
+    }
+
+    pub(crate) fn poll_ended(&self, info: &TaskInfo, start: PollStart) {
+        let now = self.inner.registry.now();
+        info.set_polled(now, start);
+        self.inner.notify_idle();
+        #[cfg(feature = "console")]
+        if let Some(console) = self.console().filter(|_| !info.internal) {
+            console.emit(Event::Poll {
+                id: info.id,
+                nanos: now - start.at,
+            });
+        }
+    }
+}
+
+impl TaskGuard {
+    pub(crate) fn info(&self) -> &Arc<TaskInfo> {
+        &self.info
+    }
+
+    #[inline]
+    pub(crate) fn complete(&mut self) {
+        #[cfg(feature = "metrics")]
+        {
+            self.completed = true;
+        }
+    }
+}
+
+impl HandleInner {
+    #[cfg(all(feature = "io", feature = "io-shared"))]
+    fn io_pending(&self) -> bool {
+        let registry = self.io.lock().unwrap().clone();
+        registry
+            .iter()
+            .filter_map(|io| io.upgrade())
+            .any(|io| io.is_busy())
+    }
+
+    #[cfg(not(all(feature = "io", feature = "io-shared")))]
+    fn io_pending(&self) -> bool {
+        false
+    }
+}
+
+impl Drop for TaskGuard {
+    fn drop(&mut self) {
+        if self.info.internal {
+            return;
+        }
+        #[cfg(feature = "metrics")]
+        {
+            let metrics = &self.inner.metrics;
+            let counter = if self.completed {
+                &metrics.tasks_completed
+            } else if std::thread::panicking() {
+                &metrics.tasks_panicked
+            } else {
+                &metrics.tasks_cancelled
+            };
+            counter.fetch_add(1, Ordering::Relaxed);
+        }
+
+        self.inner.registry.remove(self.info.id);
+        self.inner.notify_idle();
+        #[cfg(feature = "console")]
+        if let Some(console) = &self.inner.console {
+            console.emit(Event::Done { id: self.info.id });
+        }
+        if self.inner.tasks.fetch_sub(1, Ordering::AcqRel) == 1 {
+            unsafe { WakeByAddressAll(&self.inner.tasks as *const AtomicUsize as *mut c_void) };
+        }
+    }
+}
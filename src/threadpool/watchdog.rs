@@ -0,0 +1,132 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt, io,
+    sync::{Arc, Weak},
+    thread,
+    time::Duration,
+};
+
+use super::HandleInner;
+use crate::task::{TaskDump, TaskState};
+
+type Callback = Arc<dyn Fn(&WatchdogEvent) + Send + Sync>;
+
+#[derive(Debug, Clone)]
+pub enum WatchdogEvent {
+    /// A single poll of the task has been running for longer than the slow poll threshold.
+    SlowPoll { task: TaskDump, duration: Duration },
+    /// The task has been pending without being woken up for longer than the stalled threshold.
+    Stalled { task: TaskDump, duration: Duration },
+}
+
+#[derive(Clone, Default)]
+pub(crate) struct Watchdog {
+    pub(crate) slow_poll: Option<Duration>,
+    pub(crate) stalled: Option<Duration>,
+    pub(crate) callback: Option<Callback>,
+}
+
+impl Watchdog {
+    const MIN_INTERVAL: Duration = Duration::from_millis(1);
+
+    pub(crate) fn start(self, inner: Weak<HandleInner>) -> io::Result<()> {
+        // Without a callback, events only go to `tracing`.
+        if self.callback.is_none() && !cfg!(feature = "tracing") {
+            return Ok(());
+        }
+        let interval = match (self.slow_poll, self.stalled) {
+            (None, None) => return Ok(()),
+            (Some(threshold), None) | (None, Some(threshold)) => threshold,
+            (Some(slow_poll), Some(stalled)) => slow_poll.min(stalled),
+        };
+        let interval = (interval / 4).max(Self::MIN_INTERVAL);
+
+        thread::Builder::new()
+            .name("wae-watchdog".to_owned())
+            .spawn(move || {
+                // Remembers the poll or idle period each task was last reported for so every
+                // occurrence is only reported once.
+                let mut reported = HashMap::new();
+                loop {
+                    thread::sleep(interval);
+                    let inner = match inner.upgrade() {
+                        Some(inner) => inner,
+                        None => return,
+                    };
+                    self.check(&inner, &mut reported);
+                }
+            })
+            .map(drop)
+    }
+
+    fn check(&self, inner: &HandleInner, reported: &mut HashMap<u64, u64>) {
+        let now = inner.registry.now();
+        let tasks = inner.registry.snapshot();
+        let live: HashSet<_> = tasks.iter().map(|info| info.id).collect();
+        reported.retain(|id, _| live.contains(id));
+
+        for info in tasks {
+            let (since, threshold, slow) = match (info.state(), self.slow_poll, self.stalled) {
+                (TaskState::Running, Some(threshold), _) => (info.last_poll(), threshold, true),
+                (TaskState::Idle, _, Some(threshold)) => (info.idle_since(), threshold, false),
+                _ => continue,
+            };
+            let since = match since {
+                Some(since) => since,
+                None => continue,
+            };
+
+            let duration = Duration::from_nanos(now.saturating_sub(since));
+            if duration < threshold || reported.insert(info.id, since) == Some(since) {
+                continue;
+            }
+
+            let task = info.dump(now);
+            let event = if slow {
+                WatchdogEvent::SlowPoll { task, duration }
+            } else {
+                WatchdogEvent::Stalled { task, duration }
+            };
+            self.report(&event);
+        }
+    }
+
+    fn report(&self, event: &WatchdogEvent) {
+        match &self.callback {
+            Some(callback) => callback(event),
+            #[cfg(feature = "tracing")]
+            None => tracing::warn!(%event),
+            #[cfg(not(feature = "tracing"))]
+            None => {}
+        }
+    }
+}
+
+impl fmt::Display for WatchdogEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (task, duration, what) = match self {
+            WatchdogEvent::SlowPoll { task, duration } => (task, duration, "been polled"),
+            WatchdogEvent::Stalled { task, duration } => (task, duration, "not been woken up"),
+        };
+
+        write!(f, "task {}", task.id)?;
+        if let Some(name) = &task.name {
+            write!(f, " ({})", name)?;
+        }
+        write!(
+            f,
+            " spawned at {} has {} for {:?}",
+            task.location, what, duration
+        )
+    }
+}
+
+impl fmt::Debug for Watchdog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Watchdog")
+            .field("slow_poll", &self.slow_poll)
+            .field("stalled", &self.stalled)
+            .field("callback", &self.callback.is_some())
+            .finish()
+    }
+}
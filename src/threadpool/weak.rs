@@ -0,0 +1,95 @@
+use std::{
+    fmt,
+    sync::{Arc, Weak},
+};
+#[cfg(all(feature = "io", feature = "io-shared"))]
+use std::{io, sync::atomic::Ordering};
+
+use super::{Handle, HandleInner, Priority};
+
+/// A handle that doesn't keep the pool alive, created with [`Handle::downgrade`].
+#[derive(Clone)]
+pub struct WeakHandle {
+    inner: Weak<HandleInner>,
+    priority: Priority,
+    #[cfg(feature = "tracing")]
+    span: Option<tracing::Span>,
+}
+
+/// The pool an IO callback runs on, see [`WeakHandle::callback_pool`].
+#[cfg(all(feature = "io", feature = "io-shared"))]
+#[derive(Clone, Copy)]
+pub(crate) struct CallbackPool(*const HandleInner);
+
+impl Handle {
+    pub fn downgrade(&self) -> WeakHandle {
+        WeakHandle {
+            inner: Arc::downgrade(&self.inner),
+            priority: self.priority,
+            #[cfg(feature = "tracing")]
+            span: self.span.clone(),
+        }
+    }
+
+    /// Whether the pool was shut down and has cancelled its remaining tasks and IO.
+    #[cfg(all(feature = "io", feature = "io-shared"))]
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::Acquire)
+    }
+}
+
+impl WeakHandle {
+    /// Returns a handle to the pool unless it has been dropped.
+    pub fn upgrade(&self) -> Option<Handle> {
+        self.inner.upgrade().map(|inner| Handle {
+            inner,
+            priority: self.priority,
+            #[cfg(feature = "tracing")]
+            span: self.span.clone(),
+        })
+    }
+
+    /// Returns a handle to the pool unless it has been dropped or shut down, for IO objects
+    /// about to start an operation.
+    #[cfg(all(feature = "io", feature = "io-shared"))]
+    pub(crate) fn upgrade_live(&self) -> io::Result<Handle> {
+        match self.upgrade() {
+            Some(handle) if !handle.is_cancelled() => Ok(handle),
+            _ => Err(io::Error::other("the thread pool has been shut down")),
+        }
+    }
+
+    /// The pool, for a callback to use once the object it runs for may be gone.
+    ///
+    /// # Safety
+    ///
+    /// Must be called from a callback of the pool. Dropping the pool waits for its callbacks to
+    /// return, so it stays valid until then without the callback holding a reference it could
+    /// end up dropping from inside the pool.
+    #[cfg(all(feature = "io", feature = "io-shared"))]
+    pub(crate) unsafe fn callback_pool(&self) -> CallbackPool {
+        CallbackPool(self.inner.as_ptr())
+    }
+}
+
+#[cfg(all(feature = "io", feature = "io-shared"))]
+impl CallbackPool {
+    pub(crate) unsafe fn io_event_completed(self) {
+        let inner = &*self.0;
+        inner.io_events.fetch_sub(1, Ordering::AcqRel);
+        inner.notify_idle();
+    }
+
+    pub(crate) unsafe fn notify_idle(self) {
+        (*self.0).notify_idle();
+    }
+}
+
+impl fmt::Debug for WeakHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WeakHandle")
+            .field("alive", &(self.inner.strong_count() > 0))
+            .field("priority", &self.priority)
+            .finish()
+    }
+}
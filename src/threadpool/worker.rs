@@ -0,0 +1,342 @@
+use std::{
+    cell::RefCell,
+    ffi::c_void,
+    ptr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, RwLock, Weak,
+    },
+};
+
+use async_task::Runnable;
+use concurrent_queue::ConcurrentQueue;
+use winapi::um::{threadpoolapiset::SubmitThreadpoolWork, winnt::PTP_WORK};
+
+use super::{HandleInner, Priority, TaskQueue};
+use crate::task::TaskMeta;
+
+pub(crate) type Task = Runnable<TaskMeta>;
+
+/// Run queues owned by a single worker thread, one per priority.
+///
+/// Tasks woken up from a worker thread are pushed to its local queue so they're likely to run on
+/// the same thread with warm caches, without contending on the pool-wide queues. Other workers
+/// steal from it when they run out of tasks.
+///
+/// A task woken up by the task currently running on the worker goes to its LIFO slot instead and
+/// runs right after it on the same thread, which keeps message passing between tasks cheap. Only
+/// tasks of the running task's priority do, as the slot is only run by callbacks of its priority.
+pub(crate) struct Worker {
+    queues: [ConcurrentQueue<Task>; 3],
+    lifo: [Mutex<Option<Task>>; 3],
+    /// Index of the priority of the task being run, or `NOT_RUNNING`.
+    running: AtomicUsize,
+}
+
+#[derive(Default)]
+pub(crate) struct Workers {
+    list: RwLock<Vec<Arc<Worker>>>,
+}
+
+struct WorkerGuard {
+    inner: Weak<HandleInner>,
+    worker: Arc<Worker>,
+}
+
+thread_local! {
+    static WORKER: RefCell<Option<WorkerGuard>> = const { RefCell::new(None) };
+}
+
+impl Priority {
+    pub(crate) const ALL: [Priority; 3] = [Priority::High, Priority::Normal, Priority::Low];
+
+    pub(crate) fn index(self) -> usize {
+        match self {
+            Priority::High => 0,
+            Priority::Normal => 1,
+            Priority::Low => 2,
+        }
+    }
+}
+
+impl TaskQueue {
+    /// Number of tasks a single callback runs before returning to the thread pool, which lets it
+    /// run callbacks of other priorities in between.
+    pub(crate) const MAX_BATCH: usize = 32;
+
+    /// Makes sure a callback will look for tasks, submitting work unless a previous submission
+    /// hasn't been picked up yet.
+    fn notify(&self) {
+        if self.idle.load(Ordering::SeqCst) == 0
+            && self
+                .idle
+                .compare_exchange(0, 1, Ordering::SeqCst, Ordering::SeqCst)
+                .is_ok()
+        {
+            unsafe { SubmitThreadpoolWork(self.work) };
+        }
+    }
+}
+
+impl Worker {
+    /// Number of tasks that can run from the LIFO slot in a row before it gets flushed to the back
+    /// of the queue, so tasks waking each other up can't starve the rest.
+    const MAX_LIFO_POLLS: usize = 3;
+    const NOT_RUNNING: usize = usize::MAX;
+
+    fn new() -> Self {
+        Self {
+            queues: [
+                ConcurrentQueue::unbounded(),
+                ConcurrentQueue::unbounded(),
+                ConcurrentQueue::unbounded(),
+            ],
+            lifo: Default::default(),
+            running: AtomicUsize::new(Self::NOT_RUNNING),
+        }
+    }
+
+    fn queue(&self, priority: Priority) -> &ConcurrentQueue<Task> {
+        &self.queues[priority.index()]
+    }
+
+    fn lifo(&self, priority: Priority) -> Option<Task> {
+        self.lifo[priority.index()].lock().unwrap().take()
+    }
+
+    /// Pops a task from the queue or the LIFO slot, used when taking every task away from the
+    /// worker.
+    pub(crate) fn pop(&self, priority: Priority) -> Option<Task> {
+        self.queue(priority)
+            .pop()
+            .ok()
+            .or_else(|| self.lifo(priority))
+    }
+
+    #[cfg(feature = "metrics")]
+    fn len(&self, priority: Priority) -> usize {
+        let lifo = self.lifo[priority.index()].lock().unwrap().is_some();
+        self.queue(priority).len() + lifo as usize
+    }
+}
+
+impl Workers {
+    pub(crate) fn snapshot(&self) -> Vec<Arc<Worker>> {
+        self.list.read().unwrap().clone()
+    }
+
+    fn steal(&self, priority: Priority, thief: Option<&Worker>) -> Option<Task> {
+        let list = self.list.read().unwrap();
+        list.iter()
+            .filter(|worker| !thief.is_some_and(|thief| ptr::eq(&***worker, thief)))
+            .find_map(|worker| worker.queue(priority).pop().ok())
+    }
+}
+
+/// Queues a task that was woken up, to the current thread's local queue if it is a worker of the
+/// task's pool or to the pool-wide queue otherwise. Tasks woken up by the running task go to the
+/// LIFO slot unless `yielded` is set, which means they woke themselves up.
+pub(crate) fn schedule(task: Task, yielded: bool) {
+    let handle = &task.metadata().handle;
+    let priority = handle.priority;
+    if handle.inner.cancelled.load(Ordering::Acquire) {
+        #[cfg(feature = "tracing")]
+        tracing::debug!(parent: handle.span_id(), "task dropped by shutdown");
+        return;
+    }
+    #[cfg(feature = "tracing")]
+    tracing::trace!(parent: handle.span_id(), ?priority, "task scheduled");
+
+    // Once queued the task can run and be freed on another thread, along with what might be the
+    // last reference to the pool. The pool waits for its callbacks before going away so worker
+    // threads can keep using it for free. Other threads have nothing but the task to keep the pool
+    // alive until it is notified, so they hold a reference to it while pushing.
+    let inner = Arc::as_ptr(&handle.inner);
+    with_local(unsafe { &*inner }, |worker| {
+        let _task = worker.is_none().then(|| task.waker());
+        unsafe { (*inner).push_task(priority, task, yielded, worker.map(Arc::as_ref)) };
+    });
+}
+
+impl HandleInner {
+    fn push_task(&self, priority: Priority, task: Task, yielded: bool, worker: Option<&Worker>) {
+        if let Some(driver) = &self.driver {
+            driver.push(self.queue(priority), task);
+            return;
+        }
+        if let Some(worker) = worker {
+            if !yielded && worker.running.load(Ordering::Relaxed) == priority.index() {
+                // The worker runs the slot as soon as the current task returns, no need to notify.
+                let previous = worker.lifo[priority.index()].lock().unwrap().replace(task);
+                if let Some(previous) = previous {
+                    self.enqueue(priority, Some(worker), previous);
+                }
+                return;
+            }
+        }
+        self.enqueue(priority, worker, task);
+    }
+
+    fn enqueue(&self, priority: Priority, worker: Option<&Worker>, task: Task) {
+        let queue = self.queue(priority);
+        queue.queued.fetch_add(1, Ordering::SeqCst);
+        match worker {
+            Some(worker) => worker.queue(priority).push(task).unwrap(),
+            None => queue.queue.push(task).unwrap(),
+        }
+        queue.notify();
+    }
+
+    /// Number of tasks waiting in the pool-wide queue and every local queue.
+    #[cfg(feature = "metrics")]
+    pub(crate) fn queue_depth(&self, priority: Priority) -> usize {
+        let local: usize = self
+            .workers
+            .snapshot()
+            .iter()
+            .map(|worker| worker.len(priority))
+            .sum();
+        self.queue(priority).queue.len() + local
+    }
+
+    pub(crate) fn pop_task(&self, priority: Priority, worker: Option<&Worker>) -> Option<Task> {
+        let queue = self.queue(priority);
+        let task = worker
+            .and_then(|worker| worker.queue(priority).pop().ok())
+            .or_else(|| queue.queue.pop().ok())
+            .or_else(|| self.workers.steal(priority, worker))?;
+        queue.queued.fetch_sub(1, Ordering::SeqCst);
+        queue.made_room();
+        Some(task)
+    }
+}
+
+/// Runs a batch of tasks for the work object `work` of the pool `context` points to, passing
+/// them to `run`. The current thread gets registered as one of the pool's workers.
+///
+/// # Safety
+/// `context` must be the context the work object was created with.
+pub(crate) unsafe fn run_tasks(context: *mut c_void, work: PTP_WORK, mut run: impl FnMut(Task)) {
+    let inner = &*(context as *const HandleInner);
+    let priority = match Priority::ALL
+        .iter()
+        .copied()
+        .find(|&priority| inner.queue(priority).work == work)
+    {
+        Some(priority) => priority,
+        None => return,
+    };
+    let queue = inner.queue(priority);
+    queue.idle.fetch_sub(1, Ordering::SeqCst);
+
+    let mut worker = with_local(inner, |worker| worker.cloned());
+    let mut lifo_polls = 0;
+    for _ in 0..TaskQueue::MAX_BATCH {
+        let lifo = worker.as_ref().and_then(|worker| worker.lifo(priority));
+        let task = match lifo {
+            Some(task) if lifo_polls < Worker::MAX_LIFO_POLLS => {
+                lifo_polls += 1;
+                task
+            }
+            lifo => {
+                lifo_polls = 0;
+                if let Some(task) = lifo {
+                    inner.enqueue(priority, worker.as_deref(), task);
+                }
+                match inner.pop_task(priority, worker.as_deref()) {
+                    Some(task) => task,
+                    None => return,
+                }
+            }
+        };
+
+        // There are more tasks than this thread can run on its own, get another one to help.
+        if queue.queued.load(Ordering::SeqCst) > 0 {
+            queue.notify();
+        }
+
+        if worker.is_none() {
+            worker = register(&task.metadata().handle.inner);
+        }
+        match &worker {
+            Some(worker) => {
+                worker.running.store(priority.index(), Ordering::Relaxed);
+                run(task);
+                worker.running.store(Worker::NOT_RUNNING, Ordering::Relaxed);
+            }
+            None => run(task),
+        }
+    }
+
+    // Leave the rest of the work to a fresh callback.
+    if let Some(worker) = &worker {
+        if let Some(task) = worker.lifo(priority) {
+            inner.enqueue(priority, Some(worker), task);
+        }
+    }
+    if queue.queued.load(Ordering::SeqCst) > 0 {
+        queue.notify();
+    }
+}
+
+/// Calls `f` with the current thread's worker if it is one of the pool's.
+fn with_local<R>(inner: &HandleInner, f: impl FnOnce(Option<&Arc<Worker>>) -> R) -> R {
+    let mut f = Some(f);
+    let local = WORKER.try_with(|w| match &*w.borrow() {
+        Some(guard) if ptr::eq(Weak::as_ptr(&guard.inner), inner) => {
+            Some(f.take().unwrap()(Some(&guard.worker)))
+        }
+        _ => None,
+    });
+    match local {
+        Ok(Some(output)) => output,
+        _ => f.take().unwrap()(None),
+    }
+}
+
+fn register(inner: &Arc<HandleInner>) -> Option<Arc<Worker>> {
+    WORKER
+        .try_with(|w| {
+            let mut w = w.borrow_mut();
+            let guard = w.get_or_insert_with(|| {
+                let worker = Arc::new(Worker::new());
+                inner.workers.list.write().unwrap().push(worker.clone());
+                WorkerGuard {
+                    inner: Arc::downgrade(inner),
+                    worker,
+                }
+            });
+            guard.worker.clone()
+        })
+        .ok()
+}
+
+impl Drop for WorkerGuard {
+    fn drop(&mut self) {
+        let inner = match self.inner.upgrade() {
+            Some(inner) => inner,
+            None => return,
+        };
+        inner
+            .workers
+            .list
+            .write()
+            .unwrap()
+            .retain(|worker| !Arc::ptr_eq(worker, &self.worker));
+
+        // The thread is exiting, hand whatever is left over to the pool-wide queues.
+        for priority in Priority::ALL.iter().copied() {
+            let queue = inner.queue(priority);
+            while let Ok(task) = self.worker.queue(priority).pop() {
+                queue.queue.push(task).unwrap();
+            }
+            if let Some(task) = self.worker.lifo(priority) {
+                queue.queued.fetch_add(1, Ordering::SeqCst);
+                queue.queue.push(task).unwrap();
+            }
+            if queue.queued.load(Ordering::SeqCst) > 0 {
+                queue.notify();
+            }
+        }
+    }
+}
@@ -0,0 +1,122 @@
+use std::{collections::BTreeMap, sync::Mutex, task::Waker, time::Duration};
+
+use super::Instant;
+
+/// The pool's view of time, which can be paused and moved forward manually.
+pub(crate) struct Clock {
+    state: Mutex<ClockState>,
+}
+
+struct ClockState {
+    paused: bool,
+    /// Time at `real`, or the frozen time while paused.
+    base: std::time::Instant,
+    real: std::time::Instant,
+    /// Sleeps waiting for the paused clock to reach their deadline.
+    timers: BTreeMap<(std::time::Instant, u64), Waker>,
+    next_timer: u64,
+}
+
+impl Clock {
+    pub(crate) fn new(paused: bool) -> Self {
+        let now = std::time::Instant::now();
+        Self {
+            state: Mutex::new(ClockState {
+                paused,
+                base: now,
+                real: now,
+                timers: BTreeMap::new(),
+                next_timer: 0,
+            }),
+        }
+    }
+
+    pub(crate) fn now(&self) -> Instant {
+        Instant(self.state.lock().unwrap().now())
+    }
+
+    pub(crate) fn pause(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.base = state.now();
+        state.paused = true;
+    }
+
+    pub(crate) fn resume(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.real = std::time::Instant::now();
+        state.paused = false;
+        // Real timers take over from here.
+        let timers = std::mem::take(&mut state.timers);
+        drop(state);
+        timers.into_values().for_each(Waker::wake);
+    }
+
+    #[track_caller]
+    pub(crate) fn advance(&self, duration: Duration) {
+        let mut state = self.state.lock().unwrap();
+        assert!(state.paused, "the clock can only be advanced while paused");
+        state.base += duration;
+        let due = state.due();
+        drop(state);
+        due.into_iter().for_each(Waker::wake);
+    }
+
+    /// Moves the paused clock to the earliest timer, returning whether there was one.
+    pub(crate) fn advance_to_next_timer(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if !state.paused {
+            return false;
+        }
+        let next = match state.timers.keys().next() {
+            Some(&(deadline, _)) => deadline,
+            None => return false,
+        };
+        state.base = state.base.max(next);
+        let due = state.due();
+        drop(state);
+        due.into_iter().for_each(Waker::wake);
+        true
+    }
+
+    /// Registers a sleep with the paused clock, or returns `None` if it's running.
+    pub(crate) fn register(
+        &self,
+        deadline: Instant,
+        previous: Option<u64>,
+        waker: &Waker,
+    ) -> Option<u64> {
+        let mut state = self.state.lock().unwrap();
+        if let Some(id) = previous {
+            state.timers.retain(|&(_, timer), _| timer != id);
+        }
+        if !state.paused {
+            return None;
+        }
+        let id = state.next_timer;
+        state.next_timer += 1;
+        state.timers.insert((deadline.0, id), waker.clone());
+        Some(id)
+    }
+
+    pub(crate) fn deregister(&self, id: u64) {
+        let mut state = self.state.lock().unwrap();
+        state.timers.retain(|&(_, timer), _| timer != id);
+    }
+}
+
+impl ClockState {
+    fn now(&self) -> std::time::Instant {
+        if self.paused {
+            self.base
+        } else {
+            self.base + self.real.elapsed()
+        }
+    }
+
+    fn due(&mut self) -> Vec<Waker> {
+        let later = self.timers.split_off(&(self.base, u64::MAX));
+        std::mem::replace(&mut self.timers, later)
+            .into_values()
+            .collect()
+    }
+}
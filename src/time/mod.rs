@@ -0,0 +1,34 @@
+mod clock;
+mod instant;
+mod sleep;
+
+use std::time::Duration;
+
+pub(crate) use clock::Clock;
+pub use instant::Instant;
+pub use sleep::{sleep, sleep_until, timeout, Elapsed, Sleep, Timeout};
+
+use crate::threadpool::Handle;
+
+/// Stops the clock of the current pool so it only moves forward through [`advance`].
+///
+/// While every task of a [`Builder::current_thread`](crate::threadpool::Builder::current_thread)
+/// pool is idle, [`Handle::block_on`] and [`Handle::drive`] move the paused clock straight to the
+/// next timer. The clock of other pools only moves through [`advance`].
+pub fn pause() {
+    Handle::current().clock().pause()
+}
+
+/// Lets the clock of the current pool run again from where it was paused.
+pub fn resume() {
+    Handle::current().clock().resume()
+}
+
+/// Moves the paused clock of the current pool forward, waking up the sleeps that are due.
+///
+/// # Panics
+/// Panics if the clock isn't paused.
+#[track_caller]
+pub fn advance(duration: Duration) {
+    Handle::current().clock().advance(duration)
+}
@@ -0,0 +1,233 @@
+use std::{
+    error::Error,
+    ffi::c_void,
+    fmt,
+    future::Future,
+    pin::Pin,
+    ptr,
+    sync::Arc,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use atomic_waker::AtomicWaker;
+use pin_project_lite::pin_project;
+use winapi::{
+    shared::minwindef::{FILETIME, TRUE},
+    um::{
+        threadpoolapiset::{
+            CloseThreadpoolTimer, CreateThreadpoolTimer, SetThreadpoolTimer,
+            WaitForThreadpoolTimerCallbacks,
+        },
+        winnt::{PTP_CALLBACK_INSTANCE, PTP_TIMER},
+    },
+};
+
+use super::Instant;
+use crate::threadpool::{Handle, Hooks};
+
+/// Completes once the clock of its pool reaches its deadline, see [`sleep`].
+pub struct Sleep {
+    handle: Handle,
+    deadline: Instant,
+    timer: Option<Timer>,
+    paused: Option<u64>,
+}
+
+/// A Win32 timer waking up the sleep while the clock is running.
+struct Timer {
+    timer: PTP_TIMER,
+    context: Box<TimerContext>,
+}
+
+struct TimerContext {
+    waker: AtomicWaker,
+    hooks: Arc<Hooks>,
+}
+
+unsafe impl Send for Timer {}
+unsafe impl Sync for Timer {}
+
+pin_project! {
+    /// Fails with [`Elapsed`] if its future doesn't complete before the deadline, see [`timeout`].
+    pub struct Timeout<F> {
+        #[pin]
+        future: F,
+        sleep: Sleep,
+    }
+}
+
+/// Returned by [`Timeout`] when the deadline is reached first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Elapsed(());
+
+unsafe extern "system" fn callback(
+    _instance: PTP_CALLBACK_INSTANCE,
+    context: *mut c_void,
+    _timer: PTP_TIMER,
+) {
+    let context = &*(context as *const TimerContext);
+    context.hooks.thread_started();
+    context.waker.wake();
+}
+
+/// Waits until `duration` has elapsed on the clock of the current pool.
+#[track_caller]
+pub fn sleep(duration: Duration) -> Sleep {
+    sleep_until(Instant::now() + duration)
+}
+
+/// Waits until the clock of the current pool reaches `deadline`.
+#[track_caller]
+pub fn sleep_until(deadline: Instant) -> Sleep {
+    Sleep::new(Handle::current(), deadline)
+}
+
+/// Runs `future` until `duration` has elapsed on the clock of the current pool.
+#[track_caller]
+pub fn timeout<F: Future>(duration: Duration, future: F) -> Timeout<F> {
+    Timeout {
+        future,
+        sleep: sleep(duration),
+    }
+}
+
+impl Sleep {
+    pub(crate) fn new(handle: Handle, deadline: Instant) -> Self {
+        Self {
+            handle,
+            deadline,
+            timer: None,
+            paused: None,
+        }
+    }
+
+    pub fn deadline(&self) -> Instant {
+        self.deadline
+    }
+
+    pub fn is_elapsed(&self) -> bool {
+        self.handle.clock().now() >= self.deadline
+    }
+
+    fn arm(&mut self, waker: &std::task::Waker) {
+        let remaining = self.deadline - self.handle.clock().now();
+        let timer = match &self.timer {
+            Some(timer) => timer,
+            None => self.timer.insert(Timer::new(&self.handle)),
+        };
+        timer.context.waker.register(waker);
+
+        // Negative due times are relative, in 100ns intervals.
+        let due = -((remaining.as_nanos() / 100).max(1) as i64);
+        let mut due = FILETIME {
+            dwLowDateTime: due as u32,
+            dwHighDateTime: (due >> 32) as u32,
+        };
+        unsafe { SetThreadpoolTimer(timer.timer, &mut due, 0, 0) };
+    }
+}
+
+impl Timer {
+    fn new(handle: &Handle) -> Self {
+        let context = Box::new(TimerContext {
+            waker: AtomicWaker::new(),
+            hooks: handle.hooks().clone(),
+        });
+        let mut callback_environ = handle.callback_environ();
+        let timer = unsafe {
+            CreateThreadpoolTimer(
+                Some(callback),
+                &*context as *const TimerContext as *mut c_void,
+                &mut callback_environ,
+            )
+        };
+        if timer.is_null() {
+            panic!(
+                "failed to create a timer: {}",
+                std::io::Error::last_os_error()
+            );
+        }
+        Self { timer, context }
+    }
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if self.is_elapsed() {
+            if let Some(id) = self.paused.take() {
+                self.handle.clock().deregister(id);
+            }
+            return Poll::Ready(());
+        }
+
+        let previous = self.paused.take();
+        self.paused = self
+            .handle
+            .clock()
+            .register(self.deadline, previous, cx.waker());
+        if self.paused.is_none() {
+            self.arm(cx.waker());
+        }
+        Poll::Pending
+    }
+}
+
+impl<F: Future> Future for Timeout<F> {
+    type Output = Result<F::Output, Elapsed>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        if let Poll::Ready(output) = this.future.poll(cx) {
+            return Poll::Ready(Ok(output));
+        }
+        match Pin::new(this.sleep).poll(cx) {
+            Poll::Ready(()) => Poll::Ready(Err(Elapsed(()))),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl Drop for Sleep {
+    fn drop(&mut self) {
+        if let Some(id) = self.paused {
+            self.handle.clock().deregister(id);
+        }
+    }
+}
+
+impl Drop for Timer {
+    fn drop(&mut self) {
+        unsafe {
+            SetThreadpoolTimer(self.timer, ptr::null_mut(), 0, 0);
+            WaitForThreadpoolTimerCallbacks(self.timer, TRUE);
+            CloseThreadpoolTimer(self.timer);
+        }
+    }
+}
+
+impl fmt::Debug for Sleep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sleep")
+            .field("deadline", &self.deadline)
+            .finish()
+    }
+}
+
+impl<F> fmt::Debug for Timeout<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Timeout")
+            .field("deadline", &self.sleep.deadline)
+            .finish()
+    }
+}
+
+impl fmt::Display for Elapsed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("deadline has elapsed")
+    }
+}
+
+impl Error for Elapsed {}
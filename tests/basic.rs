@@ -1,4 +1,15 @@
-use wae::Threadpool;
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+use wae::{
+    task::TaskState,
+    threadpool::{Builder, Handle, Priority, WatchdogEvent},
+    Threadpool,
+};
 
 #[test]
 fn ok() {
@@ -13,3 +24,432 @@ fn err() {
     let pool = Threadpool::new().unwrap();
     pool.block_on(async { panic!() });
 }
+
+#[test]
+fn shutdown() {
+    let pool = Threadpool::new().unwrap();
+    let handle = Handle::clone(&pool);
+    let (tx, rx) = futures::channel::oneshot::channel::<()>();
+    pool.spawn(async {});
+    // Nothing can wake this one up, so it gets dropped after its first poll.
+    pool.spawn(futures::future::pending::<()>());
+    pool.spawn(rx);
+    let alive = pool.shutdown(Instant::now() + Duration::from_millis(100));
+    assert_eq!(1, alive);
+    drop(tx);
+
+    let dropped = Arc::new(AtomicUsize::new(0));
+    let guard = DropCounter(dropped.clone());
+    let task = handle.spawn(async move { drop(guard) });
+    assert_eq!(1, dropped.load(Ordering::SeqCst));
+    assert_eq!(None, futures::executor::block_on(task.cancel()));
+}
+
+struct DropCounter(Arc<AtomicUsize>);
+
+impl Drop for DropCounter {
+    fn drop(&mut self) {
+        self.0.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[test]
+fn hooks() {
+    let threads = Arc::new(AtomicUsize::new(0));
+    let polls = Arc::new(AtomicUsize::new(0));
+    let pool = Threadpool::builder()
+        .stack_reserve(1 << 20)
+        .on_thread_start({
+            let threads = threads.clone();
+            move || {
+                threads.fetch_add(1, Ordering::Relaxed);
+            }
+        })
+        .on_task_poll_start({
+            let polls = polls.clone();
+            move || {
+                polls.fetch_add(1, Ordering::Relaxed);
+            }
+        })
+        .build()
+        .unwrap();
+
+    pool.block_on(wae::task::yield_now());
+    assert!(threads.load(Ordering::Relaxed) >= 1);
+    assert!(polls.load(Ordering::Relaxed) >= 2);
+}
+
+#[test]
+fn thread_stop_hook() {
+    let (tx, rx) = mpsc::channel();
+    let tx = Mutex::new(tx);
+    let pool = Threadpool::builder()
+        .on_thread_stop(move || {
+            tx.lock().unwrap().send(()).ok();
+        })
+        // Panicking hooks are contained.
+        .on_task_poll_end(|| panic!("hook"))
+        .build()
+        .unwrap();
+
+    assert_eq!(2, pool.block_on(async { 1 + 1 }));
+    pool.shutdown(Instant::now());
+    // The thread that ran the task exits once the system reclaims it.
+    rx.recv_timeout(Duration::from_secs(60)).unwrap();
+}
+
+#[test]
+fn dump_tasks() {
+    let pool = Threadpool::new().unwrap();
+    let _pending = pool.spawn_named("pending", futures::future::pending::<()>());
+
+    let start = Instant::now();
+    let dump = loop {
+        let dump = pool.dump_tasks();
+        if dump.iter().all(|task| task.polls > 0) || start.elapsed() > Duration::from_secs(1) {
+            break dump;
+        }
+        std::thread::yield_now();
+    };
+
+    assert_eq!(1, dump.len());
+    assert_eq!(Some("pending"), dump[0].name.as_deref());
+    assert_eq!(file!(), dump[0].location.file());
+    assert_eq!(TaskState::Idle, dump[0].state);
+    assert_eq!(1, dump[0].polls);
+}
+
+#[test]
+fn watchdog() {
+    let (tx, rx) = mpsc::channel();
+    let tx = Mutex::new(tx);
+    let pool = Builder::new()
+        .slow_poll_threshold(Duration::from_millis(10))
+        .on_watchdog_event(move |event| {
+            tx.lock().unwrap().send(event.clone()).ok();
+        })
+        .build()
+        .unwrap();
+
+    pool.block_on(async { std::thread::sleep(Duration::from_millis(100)) });
+    let event = rx.recv_timeout(Duration::from_secs(1)).unwrap();
+    assert!(matches!(event, WatchdogEvent::SlowPoll { .. }));
+}
+
+#[test]
+fn nested_spawns() {
+    let pool = Threadpool::new().unwrap();
+    let sum = pool.block_on(async {
+        let handles: Vec<_> = (0..64)
+            .map(|i| {
+                wae::spawn(async move {
+                    let inner: Vec<_> = (0..64)
+                        .map(|j| {
+                            wae::spawn(async move {
+                                wae::task::yield_now().await;
+                                i * 64 + j
+                            })
+                        })
+                        .collect();
+                    let mut sum = 0;
+                    for handle in inner {
+                        sum += handle.await;
+                    }
+                    sum
+                })
+            })
+            .collect();
+        let mut sum = 0;
+        for handle in handles {
+            sum += handle.await;
+        }
+        sum
+    });
+    assert_eq!((0..64 * 64).sum::<usize>(), sum);
+}
+
+#[test]
+fn ping_pong() {
+    use futures::{channel::mpsc, SinkExt, StreamExt};
+
+    let pool = Threadpool::new().unwrap();
+    let rounds = pool.block_on(pool.spawn(async {
+        let (mut ping_tx, mut ping_rx) = mpsc::channel::<usize>(1);
+        let (mut pong_tx, mut pong_rx) = mpsc::channel::<usize>(1);
+        wae::spawn(async move {
+            while let Some(n) = ping_rx.next().await {
+                pong_tx.send(n + 1).await.unwrap();
+            }
+        });
+
+        let mut n = 0;
+        for _ in 0..1000 {
+            ping_tx.send(n).await.unwrap();
+            n = pong_rx.next().await.unwrap();
+        }
+        n
+    }));
+    assert_eq!(1000, rounds);
+}
+
+#[test]
+fn lifo_slot() {
+    let pool = Threadpool::new().unwrap();
+    for _ in 0..16 {
+        let (parent, child) = pool.block_on(pool.spawn(async {
+            let child = wae::spawn(async { std::thread::current().id() });
+            (std::thread::current().id(), child)
+        }));
+        assert_eq!(parent, pool.block_on(child));
+    }
+}
+
+#[test]
+fn lifo_slot_cap() {
+    fn chain(order: Arc<Mutex<Vec<usize>>>, i: usize) {
+        wae::spawn(async move {
+            order.lock().unwrap().push(i);
+            if i < 10 {
+                chain(order, i + 1);
+            }
+        });
+    }
+
+    let pool = Builder::new()
+        .min_threads(1)
+        .max_threads(1)
+        .build()
+        .unwrap();
+    let order = Arc::new(Mutex::new(Vec::new()));
+    let other = order.clone();
+    pool.block_on(pool.spawn(async move {
+        let order = other.clone();
+        wae::spawn(async move { order.lock().unwrap().push(0) });
+        chain(other, 1);
+    }));
+    pool.block_until_idle();
+
+    // The chain takes the slot from the first task, which still runs after at most 3 links.
+    let order = order.lock().unwrap();
+    assert_eq!(11, order.len());
+    assert!(
+        order.iter().position(|&i| i == 0).unwrap() <= 3,
+        "{:?}",
+        order
+    );
+}
+
+#[test]
+fn lifo_slot_priority() {
+    use futures::{channel::oneshot, FutureExt};
+
+    let pool = Builder::new()
+        .min_threads(1)
+        .max_threads(1)
+        .build()
+        .unwrap();
+    let mut high = (*pool).clone();
+    high.set_priority(Priority::High);
+
+    let (tx, mut rx) = oneshot::channel();
+    let (ready_tx, ready_rx) = mpsc::channel();
+    let (done_tx, done_rx) = mpsc::channel();
+    high.spawn(async move {
+        let value = futures::future::poll_fn(|cx| {
+            let poll = rx.poll_unpin(cx);
+            if poll.is_pending() {
+                ready_tx.send(()).unwrap();
+            }
+            poll
+        })
+        .await;
+        done_tx.send(value.unwrap()).unwrap();
+    });
+    ready_rx.recv_timeout(Duration::from_secs(60)).unwrap();
+
+    // Woken up by a task of another priority, so it can't wait in that task's LIFO slot.
+    pool.spawn(async move { tx.send(1).unwrap() });
+    assert_eq!(1, done_rx.recv_timeout(Duration::from_secs(60)).unwrap());
+}
+
+#[test]
+fn try_spawn() {
+    let pool = Builder::new()
+        .max_queued_tasks_for(Priority::Low, 0)
+        .build()
+        .unwrap();
+    let mut low = (*pool).clone();
+    low.set_priority(Priority::Low);
+
+    let future = low.try_spawn(async { 1 }).unwrap_err().into_inner();
+    let task = pool.try_spawn(future).unwrap();
+    assert_eq!(1, pool.block_on(task));
+    let task = pool.block_on(pool.spawn_with_backpressure(async { 2 }));
+    assert_eq!(2, pool.block_on(task));
+}
+
+#[test]
+fn backpressure() {
+    use futures::FutureExt;
+
+    let pool = Builder::new()
+        .current_thread()
+        .max_queued_tasks_for(Priority::Normal, 1)
+        .build()
+        .unwrap();
+    let first = pool.spawn(async { 1 });
+
+    let mut spawn = Box::pin(pool.spawn_with_backpressure(async { 2 }));
+    assert!((&mut spawn).now_or_never().is_none());
+    assert!(pool.try_spawn(async { 3 }).is_err());
+
+    // Running the first task makes room, which resumes the spawn.
+    let second = pool.block_on(spawn);
+    assert_eq!(1, pool.block_on(first));
+    assert_eq!(2, pool.block_on(second));
+}
+
+#[test]
+fn current_thread() {
+    let pool = Builder::new().current_thread().build().unwrap();
+    let thread = std::thread::current().id();
+    let threads = pool.block_on(async move {
+        let tasks: Vec<_> = (0..16)
+            .map(|_| wae::spawn(async { std::thread::current().id() }))
+            .collect();
+        let mut threads = vec![std::thread::current().id()];
+        for task in tasks {
+            threads.push(task.await);
+        }
+        threads
+    });
+    assert!(threads.iter().all(|&id| id == thread));
+
+    let (_tx, rx) = futures::channel::oneshot::channel::<()>();
+    pool.spawn(async {});
+    pool.spawn(futures::future::pending::<()>());
+    pool.spawn(rx);
+    assert_eq!(
+        1,
+        pool.shutdown(Instant::now() + Duration::from_millis(100))
+    );
+}
+
+#[test]
+fn drive() {
+    let wakeups = Arc::new(AtomicUsize::new(0));
+    let pool = {
+        let wakeups = wakeups.clone();
+        Builder::new()
+            .current_thread()
+            .on_wakeup(move || {
+                wakeups.fetch_add(1, Ordering::SeqCst);
+            })
+            .build()
+            .unwrap()
+    };
+
+    let (tx, rx) = mpsc::channel();
+    for i in 0..4 {
+        let tx = tx.clone();
+        pool.spawn(async move {
+            wae::task::yield_now().await;
+            tx.send(i).unwrap();
+        });
+    }
+    assert_eq!(1, wakeups.load(Ordering::SeqCst));
+    assert_eq!(1, pool.drive(1));
+    assert_eq!(7, pool.run_until_stalled());
+    assert_eq!(0, pool.run_until_stalled());
+    assert_eq!(4, rx.try_iter().count());
+}
+
+#[test]
+fn from_env() {
+    let vars = |vars: &'static [(&str, &str)]| {
+        move |name: &str| {
+            vars.iter()
+                .find(|(var, _)| *var == name)
+                .map(|(_, value)| value.into())
+        }
+    };
+
+    let lookup = vars(&[("WAE_ADAPTIVE_THREADS", "1..4"), ("WAE_PRIORITY", "low")]);
+    let pool = Builder::new()
+        .with_env_from(lookup)
+        .unwrap()
+        .build()
+        .unwrap();
+    assert_eq!(Priority::Low, pool.priority());
+    assert_eq!(2, pool.block_on(async { 1 + 1 }));
+
+    let err = Builder::new()
+        .with_env_from(vars(&[("WAE_MAX_THREADS", "many")]))
+        .unwrap_err();
+    assert!(err.to_string().contains("WAE_MAX_THREADS"));
+}
+
+#[test]
+fn weak_handle() {
+    let pool = Threadpool::new().unwrap();
+    let weak = pool.downgrade();
+    assert_eq!(2, weak.upgrade().unwrap().block_on(async { 1 + 1 }));
+    drop(pool);
+
+    let pool = Threadpool::new().unwrap();
+    let weak = pool.downgrade();
+    drop(pool);
+    assert!(weak.upgrade().is_none());
+}
+
+#[test]
+fn deterministic() {
+    fn order(seed: u64) -> Vec<usize> {
+        let pool = Builder::new().deterministic(seed).build().unwrap();
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let tasks: Vec<_> = (0..16)
+            .map(|i| {
+                let order = order.clone();
+                pool.spawn(async move {
+                    for _ in 0..4 {
+                        order.lock().unwrap().push(i);
+                        wae::task::yield_now().await;
+                    }
+                })
+            })
+            .collect();
+        pool.block_on(futures::future::join_all(tasks));
+        let order = order.lock().unwrap().clone();
+        order
+    }
+
+    assert_eq!(order(42), order(42));
+    assert_ne!(order(42), order(43));
+}
+
+#[test]
+fn wait_idle() {
+    fn spawn_work(pool: &Threadpool, done: &Arc<AtomicUsize>) {
+        for _ in 0..8 {
+            let done = done.clone();
+            pool.spawn(async move {
+                for _ in 0..4 {
+                    wae::task::yield_now().await;
+                }
+                done.fetch_add(1, Ordering::SeqCst);
+            });
+        }
+    }
+
+    for builder in [Builder::new(), Builder::new().current_thread()] {
+        let pool = builder.build().unwrap();
+        let done = Arc::new(AtomicUsize::new(0));
+        spawn_work(&pool, &done);
+        pool.block_on(async { wae::context().wait_idle().await });
+        assert_eq!(8, done.load(Ordering::SeqCst));
+
+        spawn_work(&pool, &done);
+        pool.block_until_idle();
+        assert_eq!(16, done.load(Ordering::SeqCst));
+    }
+}
@@ -0,0 +1,117 @@
+use std::{
+    io::{BufRead, BufReader, ErrorKind, Read},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use wae::threadpool::{Builder, Handle};
+
+const TIMEOUT: Duration = Duration::from_secs(60);
+
+fn free_addr() -> SocketAddr {
+    TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+}
+
+fn connect(addr: SocketAddr) -> TcpStream {
+    let stream = TcpStream::connect(addr).unwrap();
+    stream.set_read_timeout(Some(TIMEOUT)).unwrap();
+    stream
+}
+
+#[test]
+fn events() {
+    let addr = free_addr();
+    let pool = Builder::new().console(addr).build().unwrap();
+    let stream = connect(addr);
+
+    // Records are only sent to clients that were accepted, so spawn until some show up.
+    let stop = Arc::new(AtomicBool::new(false));
+    let spawner = {
+        let handle: Handle = (*pool).clone();
+        let stop = stop.clone();
+        thread::spawn(move || {
+            while !stop.load(Ordering::Relaxed) {
+                drop(handle.spawn_named("probe", async {}));
+                thread::sleep(Duration::from_millis(10));
+            }
+        })
+    };
+
+    let mut probe = None;
+    let (mut polled, mut done) = (false, false);
+    for line in BufReader::new(stream).lines() {
+        let line = line.unwrap();
+        let fields: Vec<&str> = line.split('\t').collect();
+        match (fields[0], probe.as_deref()) {
+            ("spawn", _) => {
+                assert_eq!(fields.len(), 4, "{:?}", line);
+                assert_ne!(fields[3], "wae-console");
+                if probe.is_none() && fields[3] == "probe" {
+                    assert!(fields[2].contains("console.rs"), "{:?}", line);
+                    probe = Some(fields[1].to_owned());
+                }
+            }
+            ("poll", Some(id)) if fields[1] == id => {
+                assert!(fields[2].parse::<u64>().is_ok(), "{:?}", line);
+                polled = true;
+            }
+            ("done", Some(id)) if fields[1] == id => done = true,
+            _ => {}
+        }
+        if polled && done {
+            break;
+        }
+    }
+    assert!(polled && done);
+
+    stop.store(true, Ordering::Relaxed);
+    spawner.join().unwrap();
+}
+
+#[test]
+fn shutdown() {
+    let addr = free_addr();
+    let pool = Builder::new().console(addr).build().unwrap();
+    let mut stream = connect(addr);
+
+    // The server isn't waited for, and closes its clients once stopped.
+    assert_eq!(pool.shutdown(Instant::now() + TIMEOUT), 0);
+    let mut buf = Vec::new();
+    if let Err(err) = stream.read_to_end(&mut buf) {
+        assert_ne!(err.kind(), ErrorKind::WouldBlock);
+        assert_ne!(err.kind(), ErrorKind::TimedOut);
+    }
+}
+
+#[test]
+fn drop_releases_pool() {
+    for builder in [Builder::new(), Builder::new().current_thread()] {
+        let pool = builder.console(free_addr()).build().unwrap();
+        let weak = pool.downgrade();
+        drop(pool);
+
+        let deadline = Instant::now() + TIMEOUT;
+        while weak.upgrade().is_some() {
+            assert!(Instant::now() < deadline, "the server kept the pool alive");
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+}
+
+#[test]
+fn idle() {
+    let addr = free_addr();
+    let pool = Builder::new().console(addr).build().unwrap();
+    let _stream = connect(addr);
+
+    // The server's pending accept isn't activity of the pool.
+    pool.block_on(async { wae::context().wait_idle().await });
+}
@@ -0,0 +1,129 @@
+use std::{
+    io,
+    mem::MaybeUninit,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use wae::io::{
+    fault::{Fault, Faulty, Schedule},
+    AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, IoSlice, IoSliceMut,
+};
+
+/// Reads from and writes to memory, counting the operations it sees.
+#[derive(Default)]
+struct Memory {
+    data: Vec<u8>,
+    reads: usize,
+    writes: usize,
+}
+
+impl AsyncRead for Memory {
+    unsafe fn poll_read(
+        mut self: Pin<&mut Self>,
+        _: &mut Context<'_>,
+        buf: &mut IoSliceMut<'_>,
+    ) -> Poll<io::Result<usize>> {
+        self.reads += 1;
+        let n = buf.len().min(self.data.len());
+        for (dst, src) in buf.iter_mut().zip(self.data.drain(..n)) {
+            *dst = MaybeUninit::new(src);
+        }
+        Poll::Ready(Ok(n))
+    }
+
+    fn cancel_read(self: Pin<&mut Self>, _: bool) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl AsyncWrite for Memory {
+    unsafe fn poll_write(
+        mut self: Pin<&mut Self>,
+        _: &mut Context<'_>,
+        buf: &IoSlice<'_>,
+    ) -> Poll<io::Result<usize>> {
+        self.writes += 1;
+        self.data.extend_from_slice(buf);
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn cancel_write(self: Pin<&mut Self>, _: bool) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn memory(data: &[u8]) -> Memory {
+    Memory {
+        data: data.to_vec(),
+        ..Memory::default()
+    }
+}
+
+#[wae::test]
+async fn short() -> io::Result<()> {
+    let mut io = Faulty::new(memory(b"Hello"))
+        .reads(Schedule::new().random(1.0, Fault::Short(2)))
+        .writes(Schedule::new().random(1.0, Fault::Short(1)));
+
+    let mut buf = [0; 5];
+    io.read_exact(buf.as_mut()).await?;
+    assert_eq!(&buf, b"Hello");
+    assert_eq!(3, io.get_ref().reads);
+
+    io.write_all(b"World".as_ref()).await?;
+    assert_eq!(b"World", &*io.get_ref().data);
+    assert_eq!(5, io.get_ref().writes);
+    Ok(())
+}
+
+#[wae::test]
+async fn errors() {
+    let mut io = Faulty::new(memory(b"Hello")).reads(
+        Schedule::new()
+            .then(Fault::Os(10054))
+            .then(Fault::Delay(3))
+            .then(Fault::Error(io::ErrorKind::TimedOut)),
+    );
+
+    let mut buf = [0; 5];
+    let err = io.read(buf.as_mut()).await.unwrap_err();
+    assert_eq!(Some(10054), err.raw_os_error());
+    assert_eq!(5, io.read(buf.as_mut()).await.unwrap());
+    assert_eq!(1, io.get_ref().reads);
+    let err = io.read(buf.as_mut()).await.unwrap_err();
+    assert_eq!(io::ErrorKind::TimedOut, err.kind());
+    assert_eq!(0, io.read(buf.as_mut()).await.unwrap());
+}
+
+#[wae::test]
+async fn reset() {
+    let mut io = Faulty::new(memory(b"Hello")).writes(Schedule::new().then(Fault::Reset));
+
+    let err = io.write(b"World".as_ref()).await.unwrap_err();
+    assert_eq!(io::ErrorKind::ConnectionReset, err.kind());
+    let mut buf = [0; 5];
+    let err = io.read(buf.as_mut()).await.unwrap_err();
+    assert_eq!(io::ErrorKind::ConnectionReset, err.kind());
+    assert_eq!(0, io.get_ref().reads);
+}
+
+#[wae::test]
+async fn seeded() -> io::Result<()> {
+    let mut sizes = Vec::new();
+    for _ in 0..2 {
+        let schedule = Schedule::new()
+            .random(0.5, Fault::Short(1))
+            .random(1.0, Fault::Short(4))
+            .seed(42);
+        let mut io = Faulty::new(memory(&[0; 64])).reads(schedule);
+        let mut buf = [0; 64];
+        let mut run = Vec::new();
+        while run.iter().sum::<usize>() < 64 {
+            run.push(io.read(buf.as_mut()).await?);
+        }
+        sizes.push(run);
+    }
+    assert_eq!(sizes[0], sizes[1]);
+    assert!(sizes[0].contains(&1) && sizes[0].contains(&4));
+    Ok(())
+}
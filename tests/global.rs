@@ -0,0 +1,13 @@
+use std::thread;
+use wae::threadpool::Builder;
+
+#[test]
+fn global_default() {
+    wae::set_global_default(Builder::new().max_threads(2)).unwrap();
+    assert!(wae::set_global_default(Builder::new()).is_err());
+
+    let task = thread::spawn(|| wae::spawn(async { 1 + 1 }))
+        .join()
+        .unwrap();
+    assert_eq!(2, wae::task::block_on(task));
+}
@@ -0,0 +1,28 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+#[wae::test(deterministic, seed = 42)]
+async fn deterministic() {
+    let main = std::thread::current().id();
+    let polls = Arc::new(AtomicUsize::new(0));
+    let tasks: Vec<_> = (0..8)
+        .map(|_| {
+            let polls = polls.clone();
+            wae::spawn(async move {
+                polls.fetch_add(1, Ordering::SeqCst);
+                std::thread::current().id()
+            })
+        })
+        .collect();
+    for task in tasks {
+        assert_eq!(main, task.await);
+    }
+    assert_eq!(8, polls.load(Ordering::SeqCst));
+}
+
+#[wae::test(env)]
+async fn env() {
+    assert_eq!(2, wae::spawn(async { 1 + 1 }).await);
+}
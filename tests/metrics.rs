@@ -0,0 +1,81 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use wae::{
+    threadpool::{Builder, Handle},
+    Threadpool,
+};
+
+#[test]
+fn tasks() {
+    let pool = Threadpool::new().unwrap();
+    pool.block_on(async {
+        wae::spawn(async {}).await;
+        wae::spawn(futures::future::pending::<()>()).cancel().await;
+    });
+
+    let metrics = pool.metrics();
+    assert_eq!(3, metrics.tasks_spawned);
+    assert_eq!(2, metrics.tasks_completed);
+    assert_eq!(1, metrics.tasks_cancelled);
+    assert_eq!(0, metrics.tasks_panicked);
+}
+
+#[test]
+fn adaptive_threads() {
+    fn wait_for(pool: &Handle, what: &str, f: impl Fn(u32, u32) -> bool) {
+        let deadline = Instant::now() + Duration::from_secs(30);
+        loop {
+            let metrics = pool.metrics();
+            let (min, max) = (metrics.min_threads, metrics.max_threads);
+            assert!(
+                (2..=8).contains(&min) && (min..=8).contains(&max),
+                "{}..{}",
+                min,
+                max
+            );
+            if f(min, max) {
+                return;
+            }
+            assert!(Instant::now() < deadline, "{}: {}..{}", what, min, max);
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    let pool = Builder::new().adaptive_threads(2, 8).build().unwrap();
+    let metrics = pool.metrics();
+    assert_eq!((2, 8), (metrics.min_threads, metrics.max_threads));
+
+    // Hints are clamped to the bounds and decay once the pool is idle.
+    pool.scale_hint(64).unwrap();
+    assert_eq!(8, pool.metrics().min_threads);
+    wait_for(&pool, "hint decay", |min, _| min == 2);
+
+    // Blocking tasks get extra threads to make up for them.
+    let release = Arc::new(AtomicBool::new(false));
+    let tasks: Vec<_> = (0..4)
+        .map(|_| {
+            let release = release.clone();
+            pool.spawn(async move {
+                wae::task::may_block();
+                while !release.load(Ordering::Relaxed) {
+                    thread::sleep(Duration::from_millis(1));
+                }
+            })
+        })
+        .collect();
+    wait_for(&pool, "blocking", |min, _| min >= 6);
+    release.store(true, Ordering::Relaxed);
+    pool.block_on(async {
+        for task in tasks {
+            task.await;
+        }
+    });
+    wait_for(&pool, "blocking decay", |min, _| min == 2);
+}
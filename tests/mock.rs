@@ -0,0 +1,59 @@
+use std::{io, time::Duration};
+use wae::{
+    io::{mock::Builder, AsyncReadExt, AsyncWriteExt},
+    time::Instant,
+};
+
+#[wae::test(start_paused = true)]
+async fn script() -> io::Result<()> {
+    let mut mock = Builder::new()
+        .read(b"PING")
+        .write(b"PONG")
+        .wait(Duration::from_secs(5))
+        .read(b"BYE")
+        .read_error(io::ErrorKind::ConnectionReset)
+        .build();
+
+    let mut buf = [0; 4];
+    mock.read_exact(buf.as_mut()).await?;
+    assert_eq!(&buf, b"PING");
+    mock.write_all(b"PO".as_ref()).await?;
+    mock.write_all(b"NG".as_ref()).await?;
+
+    let start = Instant::now();
+    let mut buf = [0; 8];
+    assert_eq!(3, mock.read(buf.as_mut()).await?);
+    assert_eq!(&buf[..3], b"BYE");
+    assert_eq!(Duration::from_secs(5), start.elapsed());
+
+    let err = mock.read(buf.as_mut()).await.unwrap_err();
+    assert_eq!(io::ErrorKind::ConnectionReset, err.kind());
+    assert_eq!(0, mock.read(buf.as_mut()).await?);
+    Ok(())
+}
+
+#[wae::test]
+async fn read_waits_for_write() -> io::Result<()> {
+    let mut mock = Builder::new().write(b"Hello").read(b"World").build();
+    let mut buf = [0; 5];
+    let read = wae::time::timeout(Duration::from_millis(10), mock.read(buf.as_mut()));
+    assert!(read.await.is_err());
+
+    mock.write_all(b"Hello".as_ref()).await?;
+    mock.read_exact(buf.as_mut()).await?;
+    assert_eq!(&buf, b"World");
+    Ok(())
+}
+
+#[test]
+#[should_panic(expected = "unexpected bytes written at offset 6")]
+fn unexpected_write() {
+    let mut mock = Builder::new().write(b"Hello world").build();
+    futures::executor::block_on(mock.write_all(b"Hello there".as_ref())).unwrap();
+}
+
+#[test]
+#[should_panic(expected = "actions left")]
+fn unfinished() {
+    Builder::new().read(b"Hello").build();
+}
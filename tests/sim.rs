@@ -0,0 +1,127 @@
+use std::{future::Future, io, time::Duration};
+use wae::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{
+        sim::{Host, Network},
+        TcpListener, TcpStream,
+    },
+    time::{self, Instant},
+    Threadpool,
+};
+
+type Result = io::Result<()>;
+
+/// Runs `test` on a deterministic pool simulating a network with a `server` and a `client` host.
+fn simulate<F, Fut>(latency: Duration, test: F) -> Result
+where
+    F: FnOnce(Network, Host, Host) -> Fut,
+    Fut: Future<Output = Result> + Send + 'static,
+{
+    let network = Network::new();
+    let server = network.host("server");
+    let client = network.host("client");
+    network.set_latency(latency);
+
+    let pool = Threadpool::builder()
+        .deterministic(7)
+        .start_paused(true)
+        .network(network.clone())
+        .build()?;
+    pool.block_on(test(network, server, client))
+}
+
+async fn connect(server: &Host, client: &Host) -> io::Result<(TcpListener, TcpStream, TcpStream)> {
+    let listener = server.run(TcpListener::bind(("0.0.0.0", 80))).await?;
+    let connect = client.run(TcpStream::connect(("server", 80)));
+    let (stream, (accepted, _)) = futures::try_join!(connect, listener.accept())?;
+    Ok((listener, stream, accepted))
+}
+
+#[test]
+fn latency() -> Result {
+    simulate(Duration::from_millis(50), |_, server, client| async move {
+        let start = Instant::now();
+        let (_listener, mut stream, mut accepted) = connect(&server, &client).await?;
+        assert_eq!(Duration::from_millis(100), start.elapsed());
+        assert_eq!(accepted.peer_addr()?, stream.local_addr()?);
+        assert_eq!(client.ip(), stream.local_addr()?.ip());
+
+        stream.write_all(b"Hello".as_ref()).await?;
+        let mut buf = [0; 5];
+        accepted.read_exact(buf.as_mut()).await?;
+        assert_eq!(&buf, b"Hello");
+        assert_eq!(Duration::from_millis(150), start.elapsed());
+
+        drop(stream);
+        assert_eq!(0, accepted.read(buf.as_mut()).await?);
+        Ok(())
+    })
+}
+
+#[test]
+fn partition() -> Result {
+    simulate(Duration::ZERO, |network, server, client| async move {
+        let (_listener, mut stream, mut accepted) = connect(&server, &client).await?;
+        network.partition(&server, &client);
+        stream.write_all(b"Hello".as_ref()).await?;
+
+        let mut buf = [0; 5];
+        let read = time::timeout(Duration::from_secs(1), accepted.read_exact(buf.as_mut()));
+        assert!(read.await.is_err());
+
+        network.heal(&server, &client);
+        accepted.read_exact(buf.as_mut()).await?;
+        assert_eq!(&buf, b"Hello");
+        Ok(())
+    })
+}
+
+#[test]
+fn link_failure() -> Result {
+    simulate(Duration::ZERO, |network, server, client| async move {
+        let (_listener, mut stream, mut accepted) = connect(&server, &client).await?;
+        network.fail_link(&server, &client);
+
+        let mut buf = [0; 5];
+        let err = accepted.read(buf.as_mut()).await.unwrap_err();
+        assert_eq!(io::ErrorKind::ConnectionReset, err.kind());
+        let err = stream.write_all(b"Hello".as_ref()).await.unwrap_err();
+        assert_eq!(io::ErrorKind::ConnectionReset, err.kind());
+
+        let err = client
+            .run(TcpStream::connect(("server", 80)))
+            .await
+            .unwrap_err();
+        assert_eq!(io::ErrorKind::HostUnreachable, err.kind());
+
+        network.restore_link(&server, &client);
+        client.run(TcpStream::connect(("server", 80))).await?;
+        Ok(())
+    })
+}
+
+#[test]
+fn resolution() -> Result {
+    simulate(Duration::ZERO, |_, _, _| async {
+        let err = TcpStream::connect("nowhere:80").await.unwrap_err();
+        assert_eq!(io::ErrorKind::NotFound, err.kind());
+        let err = TcpStream::connect("localhost:80").await.unwrap_err();
+        assert_eq!(io::ErrorKind::ConnectionRefused, err.kind());
+        Ok(())
+    })
+}
+
+#[test]
+fn concurrent_accepts() -> Result {
+    simulate(Duration::from_millis(10), |_, server, client| async move {
+        let listener = server.run(TcpListener::bind(("0.0.0.0", 80))).await?;
+        let accepts = futures::future::try_join(listener.accept(), listener.accept());
+        let connects = futures::future::try_join(
+            client.run(TcpStream::connect(("server", 80))),
+            client.run(TcpStream::connect(("server", 80))),
+        );
+        let ((first, _), (second, _)) = futures::try_join!(accepts, connects)?.0;
+        assert_ne!(first.peer_addr()?, second.peer_addr()?);
+        Ok(())
+    })
+}
@@ -1,8 +1,9 @@
 use futures::{StreamExt, TryStreamExt};
-use std::net::SocketAddr;
+use std::{net::SocketAddr, time::Instant};
 use wae::{
     io::{AsyncReadExt, AsyncWriteExt},
     net::{TcpListener, TcpStream},
+    Threadpool,
 };
 
 type Result = std::io::Result<()>;
@@ -41,3 +42,44 @@ async fn handle(mut stream: TcpStream) -> Result {
     assert_eq!(&buf, b"Hello");
     stream.write_all(b"World".as_ref()).await
 }
+
+#[wae::test]
+async fn stats() -> Result {
+    let listener = TcpListener::bind(("localhost", 0)).await?;
+    let addr = listener.local_addr()?;
+    let client = wae::spawn(client(addr));
+
+    let (mut stream, _) = listener.accept().await?;
+    let mut buf = [0; 5];
+    stream.read_exact(buf.as_mut()).await?;
+    stream.write_all(b"World".as_ref()).await?;
+    client.await?;
+
+    let stats = stream.stats();
+    assert_eq!(stats.bytes_read, 5);
+    assert_eq!(stats.bytes_written, 5);
+    assert_eq!(stats.read_errors + stats.write_errors, 0);
+    assert!(stats.last_read.is_some() && stats.last_write.is_some());
+
+    let stats = listener.stats();
+    assert_eq!((stats.accepted, stats.failed), (1, 0));
+    assert!(stats.last_accept.is_some());
+    Ok(())
+}
+
+#[test]
+fn shut_down_pool() -> Result {
+    let pool = Threadpool::new()?;
+    let weak = pool.downgrade();
+    let (mut stream, _listener) = pool.block_on(async {
+        let listener = TcpListener::bind(("localhost", 0)).await?;
+        let stream = TcpStream::connect(listener.local_addr()?).await?;
+        Ok::<_, std::io::Error>((stream, listener))
+    })?;
+    assert!(weak.upgrade().is_some());
+
+    assert_eq!(0, pool.shutdown(Instant::now()));
+    let err = futures::executor::block_on(stream.write_all(b"Hello".as_ref())).unwrap_err();
+    assert_eq!(std::io::ErrorKind::Other, err.kind());
+    Ok(())
+}
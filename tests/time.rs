@@ -0,0 +1,78 @@
+use std::{
+    io,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use wae::{
+    threadpool::Builder,
+    time::{self, Instant},
+    Threadpool,
+};
+
+#[wae::test(start_paused = true)]
+async fn auto_advance() {
+    let start = Instant::now();
+    let real = std::time::Instant::now();
+    let sleeps: Vec<_> = (1..=3)
+        .map(|hours| wae::spawn(time::sleep(Duration::from_secs(hours * 3600))))
+        .collect();
+    for sleep in sleeps {
+        sleep.await;
+    }
+    assert_eq!(Duration::from_secs(3 * 3600), start.elapsed());
+    assert!(real.elapsed() < Duration::from_secs(60));
+}
+
+#[wae::test(start_paused = true)]
+async fn timeout() {
+    let pending = time::timeout(Duration::from_secs(5), futures::future::pending::<()>());
+    assert!(pending.await.is_err());
+    let ready = time::timeout(Duration::from_secs(5), async { 1 + 1 });
+    assert_eq!(Ok(2), ready.await);
+}
+
+#[test]
+fn advance() {
+    let pool = Threadpool::new().unwrap();
+    pool.block_on(async {
+        time::pause();
+        let start = Instant::now();
+        let sleep = wae::spawn(time::sleep(Duration::from_secs(10)));
+        time::advance(Duration::from_secs(10));
+        sleep.await;
+        assert_eq!(Duration::from_secs(10), start.elapsed());
+
+        time::resume();
+        time::sleep(Duration::from_millis(10)).await;
+        assert!(start.elapsed() >= Duration::from_millis(10_010));
+    });
+}
+
+#[test]
+fn drive_advances() {
+    let pool = Builder::new()
+        .current_thread()
+        .start_paused(true)
+        .build()
+        .unwrap();
+    let done = Arc::new(AtomicBool::new(false));
+    let flag = done.clone();
+    pool.spawn(async move {
+        time::sleep(Duration::from_secs(3600)).await;
+        flag.store(true, Ordering::SeqCst);
+    });
+
+    let real = std::time::Instant::now();
+    assert_eq!(2, pool.run_until_stalled());
+    assert!(done.load(Ordering::SeqCst));
+    assert!(real.elapsed() < Duration::from_secs(60));
+}
+
+#[test]
+fn start_paused_needs_current_thread() {
+    let err = Builder::new().start_paused(true).build().unwrap_err();
+    assert_eq!(io::ErrorKind::InvalidInput, err.kind());
+}